@@ -1,21 +1,28 @@
-use clap::{Parser, ValueEnum};
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
 use colored::Colorize;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
-use inquire::MultiSelect;
+use inquire::{Confirm, MultiSelect, Text};
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState},
+    widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Sparkline, Table, TableState, Wrap},
+    TerminalOptions, Viewport,
 };
-use std::collections::{HashMap, HashSet};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
-use std::io::stdout;
+use std::io::{stdout, BufReader, IsTerminal, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
-use std::time::{Duration, Instant};
-use sysinfo::System;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use sysinfo::{Process, System, ThreadKind, Users};
 use terminal_size::{terminal_size, Width};
 
 #[cfg(unix)]
@@ -35,6 +42,50 @@ enum SortBy {
     Pid,
     Name,
     Port,
+    Children,
+    Args,
+    LastActive,
+    CpuTime,
+    Sockets,
+    FdPct,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq)]
+enum MemKind {
+    #[default]
+    Rss,
+    Virtual,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq)]
+enum ConfirmStyle {
+    Plain,
+    #[default]
+    Fancy,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq)]
+enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+    Mono,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+enum DedupeBy {
+    Name,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+enum OutputFormat {
+    Tsv,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+enum GroupBy {
+    User,
 }
 
 #[derive(Parser)]
@@ -48,36 +99,514 @@ struct Args {
     #[arg(short, long)]
     filter: Option<String>,
 
+    /// Load defaults from the [profiles.NAME] section of the config file; explicit flags still win
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// If a numeric --filter matches no process names, match it against PIDs instead
+    #[arg(long = "smart-filter", requires = "filter")]
+    smart_filter: bool,
+
     /// Signal to send (default SIGKILL)
     #[arg(short, long, default_value = "KILL")]
     signal: String,
 
+    /// Filter expression over cpu, mem, name, pid, port and user, e.g. 'cpu>50 and name~node'
+    #[arg(long, value_name = "EXPR")]
+    query: Option<String>,
+
     /// Sort processes by field (default: cpu)
     #[arg(long, value_enum, default_value = "cpu")]
     sort: SortBy,
 
+    /// Randomize row order on every refresh (testing aid for selection tracking)
+    #[arg(long, hide = true)]
+    shuffle: bool,
+
+    /// Only list the first N processes after sorting
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+
+    /// Shortcut for --sort cpu --limit N
+    #[arg(long = "top-cpu", value_name = "N", conflicts_with_all = ["sort", "top_mem", "limit"])]
+    top_cpu: Option<usize>,
+
+    /// Shortcut for --sort mem --limit N
+    #[arg(long = "top-mem", value_name = "N", conflicts_with_all = ["sort", "limit"])]
+    top_mem: Option<usize>,
+
+    /// Which memory figure to show and sort by
+    #[arg(long = "mem-kind", value_enum, default_value = "rss")]
+    mem_kind: MemKind,
+
+    /// Order the interactive selector by a different field than --sort
+    #[arg(long = "selector-sort", value_enum, value_name = "FIELD")]
+    selector_sort: Option<SortBy>,
+
     /// Live mode with auto-refreshing processes list
     #[arg(short, long)]
     live: bool,
 
+    /// Live mode refresh interval in milliseconds (0 = refresh when processes start or exit,
+    /// polling /proc/loadavg and redrawing at most once a second)
+    #[arg(long, value_name = "MS", default_value = "2000")]
+    interval: u64,
+
+    /// Exit live mode after this many refreshes without killing (0 = run forever)
+    #[arg(long = "refresh-count", value_name = "N", default_value = "0")]
+    refresh_count: u64,
+
+    /// Highlight live rows whose memory grew by more than this many MB since first seen
+    #[arg(long = "mem-alert", value_name = "MB", requires = "live")]
+    mem_alert: Option<u64>,
+
+    /// Ring the terminal bell when a process first crosses --mem-alert
+    #[arg(long, requires = "mem_alert")]
+    beep: bool,
+
+    /// Compute live CPU % from CPU time used over at least this window instead of per refresh (Linux only)
+    #[arg(long = "cpu-window", value_name = "MS", requires = "live")]
+    cpu_window: Option<u64>,
+
+    /// Draw live mode inline instead of on the alternate screen, leaving the last frame in scrollback
+    #[arg(long = "no-alt-screen", requires = "live")]
+    no_alt_screen: bool,
+
+    /// Append timestamp, pid, name, cpu and memory for every row to this CSV file on each live refresh
+    #[arg(long = "log-csv", value_name = "PATH", requires = "live")]
+    log_csv: Option<PathBuf>,
+
+    /// Adapt the live refresh interval to how much system CPU is changing
+    #[arg(long)]
+    adaptive: bool,
+
     /// Show only processes with open ports
     #[arg(long)]
     ports: bool,
 
+    /// List one row per listening port and protocol, sorted by port
+    #[arg(long = "by-port")]
+    by_port: bool,
+
+    /// Also list ports whose owning PID no longer maps to a running process, named <unknown>
+    #[arg(long = "show-orphan-ports")]
+    show_orphan_ports: bool,
+
+    /// Only show listeners bound to wildcard or non-loopback addresses
+    #[arg(long = "public-only")]
+    public_only: bool,
+
+    /// Show whether each port is bound on IPv4, IPv6 or both
+    #[arg(long = "show-stack")]
+    show_stack: bool,
+
+    /// Keep the protocol next to every port even when all listed ports share one
+    #[arg(long = "always-show-proto")]
+    always_show_proto: bool,
+
+    /// Show the address each port is bound to
+    #[arg(long = "show-addr")]
+    show_addr: bool,
+
     /// Filter by specific port number
     #[arg(long, value_name = "PORT")]
     port: Option<u16>,
 
+    /// Filter by an inclusive range of port numbers, e.g. 3000-3010
+    #[arg(
+        long = "port-range",
+        value_name = "START-END",
+        value_parser = parse_port_range,
+        conflicts_with = "port"
+    )]
+    port_range: Option<PortRange>,
+
+    /// Only show listeners matching PROTO:PORT, e.g. tcp:443; repeat to match any of several
+    #[arg(long, value_name = "PROTO:PORT", value_parser = parse_listen_spec)]
+    listening: Vec<ListenSpec>,
+
+    /// Wait until PORT is free (--until-free) or taken (--until-taken), polling every --interval, then exit
+    #[arg(long = "watch-port", value_name = "PORT", conflicts_with_all = ["live", "group_by", "histogram"])]
+    watch_port: Option<u16>,
+
+    /// With --watch-port, wait until nothing is listening on the port
+    #[arg(long = "until-free", requires = "watch_port", conflicts_with = "until_taken")]
+    until_free: bool,
+
+    /// With --watch-port, wait until something is listening on the port
+    #[arg(long = "until-taken", requires = "watch_port")]
+    until_taken: bool,
+
+    /// Kill only the longest-running matching process
+    #[arg(long = "kill-oldest", conflicts_with = "kill_newest")]
+    kill_oldest: bool,
+
+    /// Kill only the most recently started matching process
+    #[arg(long = "kill-newest")]
+    kill_newest: bool,
+
     /// Nuke all matching processes with pre-confirmation to the filter (-f) or the port (--port)
     #[arg(long = "confirm-nuke")]
     confirm_nuke: bool,
+
+    /// Always ask before killing, even when --pid names every target exactly
+    #[arg(long)]
+    confirm: bool,
+
+    /// After killing, re-sample and reopen the selector until nothing is selected
+    #[arg(
+        long = "loop",
+        conflicts_with_all = ["live", "confirm_nuke", "exec", "kill_oldest", "kill_newest", "pids"]
+    )]
+    loop_selector: bool,
+
+    /// Keep re-running the --confirm-nuke kill until no matches remain
+    #[arg(
+        long,
+        requires = "confirm_nuke",
+        conflicts_with_all = ["live", "exec", "step", "pids"]
+    )]
+    repeat: bool,
+
+    /// Milliseconds to wait between --repeat rounds
+    #[arg(long = "repeat-interval", value_name = "MS", default_value = "2000", requires = "repeat")]
+    repeat_interval: u64,
+
+    /// Give up after this many --repeat rounds (0 = no limit)
+    #[arg(long = "repeat-max", value_name = "N", default_value = "10", requires = "repeat")]
+    repeat_max: u32,
+
+    /// Print a resource usage summary grouped by field and exit
+    #[arg(long = "group-by", value_enum, value_name = "FIELD")]
+    group_by: Option<GroupBy>,
+
+    /// Print a histogram of processes by CPU and memory usage and exit
+    #[arg(long, conflicts_with_all = ["live", "group_by"])]
+    histogram: bool,
+
+    /// Compare two TSV snapshots (from `e` or --output tsv) and print added, removed and changed processes
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        conflicts_with_all = ["live", "histogram", "group_by"]
+    )]
+    diff: Vec<PathBuf>,
+
+    /// Read process data from an alternate procfs root (Linux only)
+    #[arg(long = "proc-path", value_name = "DIR")]
+    proc_path: Option<PathBuf>,
+
+    /// Confirm each selected process individually before killing it
+    #[arg(long)]
+    step: bool,
+
+    /// Treat a port as dangerous to kill (repeatable, adds to the config list)
+    #[arg(long = "danger-port", value_name = "PORT")]
+    danger_ports: Vec<u16>,
+
+    /// Show whether each process is its terminal's foreground job (Linux only)
+    #[arg(long = "show-fg")]
+    show_fg: bool,
+
+    /// Show matching processes as a parent/child tree
+    #[arg(long, conflicts_with_all = ["ports", "port", "by_port", "public_only", "selector_sort"])]
+    tree: bool,
+
+    /// In --tree, order siblings by start time and number them
+    #[arg(long = "show-spawn-order", requires = "tree")]
+    show_spawn_order: bool,
+
+    /// Show each process's controlling terminal (Linux only)
+    #[arg(long = "show-tty")]
+    show_tty: bool,
+
+    /// Show the total CPU time each process has used, as H:MM:SS (Linux only)
+    #[arg(long = "show-cputime")]
+    show_cputime: bool,
+
+    /// Show how many sockets each process holds, in any state (Linux only)
+    #[arg(long = "show-sockets")]
+    show_sockets: bool,
+
+    /// Show open file descriptors as a percentage of the soft limit (Linux only)
+    #[arg(long = "show-fd-usage")]
+    show_fd_usage: bool,
+
+    /// Show the user each process runs as
+    #[arg(long = "show-owner")]
+    show_owner: bool,
+
+    /// Only show processes owned by this user (name, UID, or `me`)
+    #[arg(long, value_name = "USER")]
+    user: Option<String>,
+
+    /// Show each process's niceness, -20 (highest priority) to 19 (Linux only)
+    #[arg(long = "show-prio")]
+    show_prio: bool,
+
+    /// Only show processes with at least this niceness (Linux only)
+    #[arg(long = "min-prio", value_name = "NICE", allow_hyphen_values = true)]
+    min_prio: Option<i32>,
+
+    /// Only show processes with at most this niceness (Linux only)
+    #[arg(long = "max-prio", value_name = "NICE", allow_hyphen_values = true)]
+    max_prio: Option<i32>,
+
+    /// Only show processes with a controlling terminal (Linux only)
+    #[arg(long = "only-with-tty", conflicts_with = "no_tty")]
+    only_with_tty: bool,
+
+    /// Only show processes without a controlling terminal, such as daemons (Linux only)
+    #[arg(long = "no-tty")]
+    no_tty: bool,
+
+    /// Fold thread rows into their process, counting their CPU in the process row (Linux only)
+    #[arg(long = "sum-thread-cpu")]
+    sum_thread_cpu: bool,
+
+    /// Collapse matches to one row per value, keeping the busiest instance and a count
+    #[arg(long = "dedupe-by", value_enum, value_name = "FIELD")]
+    dedupe_by: Option<DedupeBy>,
+
+    /// Show the number of direct child processes
+    #[arg(long = "show-children")]
+    show_children: bool,
+
+    /// Confirmation prompt style; defaults to plain [y/N] lines when not on a full, non-dumb terminal
+    #[arg(long = "confirm-style", value_enum, value_name = "STYLE")]
+    confirm_style: Option<ConfirmStyle>,
+
+    /// Color palette for the list and live table; mono turns color off
+    #[arg(long, value_enum, value_name = "THEME", default_value = "dark")]
+    theme: ThemeName,
+
+    /// Hide the selector's column header and help line
+    #[arg(long, alias = "header-off")]
+    minimal: bool,
+
+    /// Print a one-line count, memory and CPU total of the matches before the selector opens
+    #[arg(long)]
+    summary: bool,
+
+    /// Where to shorten names that don't fit their column
+    #[arg(long, value_enum, default_value = "end")]
+    truncate: Truncate,
+
+    /// Text placed between columns in list output
+    #[arg(long, value_name = "STR", default_value = " ")]
+    separator: String,
+
+    /// Spaces added on each side of the column separator
+    #[arg(long, value_name = "N", default_value = "0")]
+    pad: usize,
+
+    /// Only show processes in this session ID (Linux only)
+    #[arg(long, value_name = "SID", conflicts_with = "current_session")]
+    session: Option<u32>,
+
+    /// Only show processes in rip's own terminal session (Linux only)
+    #[arg(long = "current-session")]
+    current_session: bool,
+
+    /// Also signal every other process in the sessions of the selected processes, leaders last (Linux only)
+    #[arg(long = "session-kill", conflicts_with_all = ["live", "exec"])]
+    session_kill: bool,
+
+    /// Also signal every descendant of the selected processes, children before their parents
+    #[arg(long = "kill-tree", conflicts_with = "live")]
+    kill_tree: bool,
+
+    /// Don't re-check for processes that survived the signal
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Only show processes whose executable has this SHA-256 (Linux only)
+    #[arg(long = "exe-hash", value_name = "SHA256")]
+    exe_hash: Option<String>,
+
+    /// Keep processes whose name contains PATTERN at the top of the list (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    pin: Vec<String>,
+
+    /// Only match these exact PIDs and signal them without the selector or confirmation
+    #[arg(long = "pid", value_name = "PID", num_args = 1..)]
+    pids: Vec<u32>,
+
+    /// Only show processes holding this file, or anything under this directory, open (Linux only)
+    #[arg(long = "has-open", value_name = "PATH")]
+    has_open: Option<PathBuf>,
+
+    /// Print the names of matching processes and exit without killing
+    #[arg(long, conflicts_with_all = ["live", "group_by"])]
+    names: bool,
+
+    /// Write the PIDs of matching processes to FILE ("-" for stdout) and exit without killing
+    #[arg(long = "write-pids", value_name = "FILE", conflicts_with_all = ["live", "group_by", "names"])]
+    write_pids: Option<PathBuf>,
+
+    /// Print matching processes in a machine-readable format and exit without killing
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with_all = ["live", "names", "write_pids"]
+    )]
+    output: Option<OutputFormat>,
+
+    /// Start --output tsv with a `#`-prefixed row naming the columns
+    #[arg(long = "tsv-header", requires = "output")]
+    tsv_header: bool,
+
+    /// Report errors on stderr as JSON objects with `error` and `code` fields
+    #[arg(long = "error-json")]
+    error_json: bool,
+
+    /// Separate --names/--write-pids entries with NUL bytes instead of newlines
+    #[arg(long)]
+    null: bool,
+
+    /// Run a command on the selection instead of killing; {} is replaced by each PID, otherwise all PIDs are appended
+    #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["live", "names", "write_pids"])]
+    exec: Option<String>,
+
+    /// Poll every --interval ms and run COMMAND each time the filters start matching; {} is replaced by the matched PIDs
+    #[arg(
+        long = "on-match",
+        value_name = "COMMAND",
+        conflicts_with_all = ["live", "names", "write_pids", "exec", "confirm_nuke", "output"]
+    )]
+    on_match: Option<String>,
+
+    /// Send SIGCHLD to the parent of selected zombies without asking
+    #[arg(long = "reap-zombies")]
+    reap_zombies: bool,
+
+    /// Refuse to kill without a narrowing filter and always confirm first (also `safe = true` in config)
+    #[arg(long)]
+    safe: bool,
+
+    /// Allow rip to list and signal its own process
+    #[arg(long = "include-self")]
+    include_self: bool,
+
+    /// Allow signalling the shell rip was started from and its ancestors up to the session leader
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq)]
+enum Truncate {
+    Start,
+    Middle,
+    #[default]
+    End,
 }
 
 fn truncate(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        s.to_string()
-    } else {
-        format!("{}...", &s[..max_len - 3])
+    truncate_with(s, max_len, Truncate::End)
+}
+
+fn truncate_with(s: &str, max_len: usize, strategy: Truncate) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.len() <= max_len {
+        return s.to_string();
+    }
+    if max_len <= 3 {
+        return chars[..max_len].iter().collect();
+    }
+
+    let keep = max_len - 3;
+    let front = match strategy {
+        Truncate::End => keep,
+        Truncate::Start => 0,
+        Truncate::Middle => keep.div_ceil(2),
+    };
+    let head: String = chars[..front].iter().collect();
+    let tail: String = chars[chars.len() - (keep - front)..].iter().collect();
+    format!("{}...{}", head, tail)
+}
+
+// Shared by the selector, its header and the live table so the columns line up.
+const CHILDREN_WIDTH: usize = 8;
+const SOCKETS_WIDTH: usize = 7;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Columns {
+    children: bool,
+    foreground: bool,
+    tty: bool,
+    cpu_time: bool,
+    sockets: bool,
+    fd_usage: bool,
+    prio: bool,
+    owner: bool,
+    bind_addr: bool,
+    stack: bool,
+    mem_growth: bool,
+    hide_usage: bool,
+    truncate: Truncate,
+    single_protocol: Option<&'static str>,
+    settings: Settings,
+}
+
+impl Columns {
+    fn from_args(args: &Args, settings: Settings) -> Self {
+        Columns {
+            children: args.show_children,
+            foreground: args.show_fg,
+            tty: args.show_tty,
+            cpu_time: args.show_cputime,
+            sockets: args.show_sockets,
+            fd_usage: args.show_fd_usage,
+            prio: args.show_prio,
+            owner: args.show_owner,
+            bind_addr: args.show_addr || args.public_only,
+            stack: args.show_stack,
+            mem_growth: args.mem_alert.is_some(),
+            hide_usage: args.by_port,
+            truncate: args.truncate,
+            single_protocol: None,
+            settings,
+        }
+    }
+
+    fn extra_width(&self) -> isize {
+        let mut width = 0;
+        if self.hide_usage {
+            width -= 17;
+        }
+        if self.children {
+            width += CHILDREN_WIDTH as isize + 1;
+        }
+        if self.foreground {
+            width += 3;
+        }
+        if self.tty {
+            width += 8;
+        }
+        if self.cpu_time {
+            width += 10;
+        }
+        if self.sockets {
+            width += SOCKETS_WIDTH as isize + 1;
+        }
+        if self.fd_usage {
+            width += 7;
+        }
+        if self.prio {
+            width += 5;
+        }
+        if self.owner {
+            width += OWNER_WIDTH as isize + 1;
+        }
+        if self.bind_addr {
+            width += 16;
+        }
+        if self.stack {
+            width += 6;
+        }
+        width
     }
 }
 
@@ -85,7 +614,10 @@ fn calculate_name_width(ports_mode: bool) -> usize {
     let term_width = terminal_size()
         .map(|(Width(w), _)| w as usize)
         .unwrap_or(80);
+    name_width_for(term_width, ports_mode)
+}
 
+fn name_width_for(term_width: usize, ports_mode: bool) -> usize {
     let mut fixed = 6 + 7 + 7 + 9 + 4;
 
     if ports_mode {
@@ -96,7 +628,7 @@ fn calculate_name_width(ports_mode: bool) -> usize {
     available.clamp(15, 80)
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 struct ProcessInfo {
     pid: u32,
     name: String,
@@ -105,708 +637,8023 @@ struct ProcessInfo {
     name_width: usize,
     port: Option<u16>,
     protocol: Option<String>,
+    bind_addr: Option<IpAddr>,
+    stack: &'static str,
+    owner: Option<String>,
+    start_time: u64,
+    start_ticks: Option<u64>,
+    run_time: u64,
+    is_self: bool,
+    is_shell: bool,
+    pinned: bool,
+    danger: bool,
+    child_count: usize,
+    parent: Option<u32>,
+    thread_of: Option<u32>,
+    duplicates: usize,
+    last_active: Option<u64>,
+    zombie: bool,
+    tree_prefix: String,
+    mem_growth: i64,
+    cpu_ticks: Option<u64>,
+    cpu_time: Option<u64>,
+    socket_count: Option<usize>,
+    fd_usage: Option<f32>,
+    nice: Option<i32>,
+    session: Option<u32>,
+    foreground: Option<bool>,
+    tty: Option<String>,
+    cmd: Vec<String>,
+    pidfd: Option<Arc<PidFd>>,
+    stale: bool,
+    columns: Columns,
 }
 
 impl fmt::Display for ProcessInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let display_name = truncate(&self.name, self.name_width);
+        let theme = self.columns.settings.theme;
+        let label = format!("{}{}", self.tree_prefix, display_label(self));
+        let display_name = truncate_with(&label, self.name_width, self.columns.truncate);
         let pid_formatted = format!("{:<7}", self.pid);
         let name_formatted = format!("{:<width$}", display_name, width = self.name_width);
         let cpu_formatted = format!("{:>6.1}%", self.cpu);
-        let mem_formatted = format!("{:>9}", format!("{} MB", self.memory));
+        let mem_formatted = format!("{:>9}", format_memory(self.memory));
 
-        let pid_str = Colorize::dimmed(pid_formatted.as_str());
-        let name_str = Colorize::white(name_formatted.as_str());
+        let pid_str = paint(pid_formatted.as_str(), theme.dim);
+        let name_str = if self.is_self || self.is_shell {
+            Colorize::italic(paint(name_formatted.as_str(), theme.own))
+        } else {
+            paint(name_formatted.as_str(), theme.text)
+        };
         let cpu_colored = if self.cpu > 50.0 {
-            Colorize::bold(Colorize::red(cpu_formatted.as_str()))
+            Colorize::bold(paint(cpu_formatted.as_str(), theme.hot))
         } else if self.cpu > 10.0 {
-            Colorize::yellow(cpu_formatted.as_str())
+            paint(cpu_formatted.as_str(), theme.warm)
         } else {
-            Colorize::dimmed(cpu_formatted.as_str())
+            paint(cpu_formatted.as_str(), theme.dim)
         };
-        let mem_colored = if self.memory > 500 {
-            Colorize::bold(Colorize::red(mem_formatted.as_str()))
-        } else if self.memory > 100 {
-            Colorize::yellow(mem_formatted.as_str())
+        let mem_colored = if self.memory > 500 * MB {
+            Colorize::bold(paint(mem_formatted.as_str(), theme.hot))
+        } else if self.memory > 100 * MB {
+            paint(mem_formatted.as_str(), theme.warm)
         } else {
-            Colorize::dimmed(mem_formatted.as_str())
+            paint(mem_formatted.as_str(), theme.dim)
         };
 
+        let mut parts = Vec::new();
         if let Some(port) = self.port {
-            let proto = self.protocol.as_deref().unwrap_or("TCP");
-            let port_formatted = format!("{:<5} {:>3}", port, proto);
-            let port_str = Colorize::green(port_formatted.as_str());
-            write!(
-                f,
-                "{} {} {} {} {}",
-                port_str, pid_str, name_str, cpu_colored, mem_colored
-            )
-        } else {
-            write!(f, "{} {} {} {}", pid_str, name_str, cpu_colored, mem_colored)
+            let port_formatted = match self.columns.single_protocol {
+                Some(_) => format!("{:<9}", port),
+                None => format!("{:<5} {:>3}", port, self.protocol.as_deref().unwrap_or("TCP")),
+            };
+            let port_str = paint(port_formatted.as_str(), theme.port(self));
+            let port_str = if self.danger { Colorize::bold(port_str) } else { port_str };
+            parts.push(port_str.to_string());
         }
+        if self.columns.stack {
+            let stack_formatted = format!("{:<5}", self.stack);
+            parts.push(paint(stack_formatted.as_str(), theme.dim).to_string());
+        }
+        if self.columns.bind_addr {
+            let addr_formatted = format!("{:<15}", addr_label(self.bind_addr));
+            parts.push(paint(addr_formatted.as_str(), theme.dim).to_string());
+        }
+        parts.extend([pid_str.to_string(), name_str.to_string()]);
+        if !self.columns.hide_usage {
+            parts.extend([cpu_colored.to_string(), mem_colored.to_string()]);
+        }
+        if self.columns.children {
+            let children_formatted = format!("{:>width$}", self.child_count, width = CHILDREN_WIDTH);
+            parts.push(paint(children_formatted.as_str(), theme.dim).to_string());
+        }
+        if self.columns.foreground {
+            let fg_formatted = format!("{:<2}", foreground_label(self.foreground));
+            parts.push(if self.foreground == Some(true) {
+                paint(fg_formatted.as_str(), theme.accent).to_string()
+            } else {
+                paint(fg_formatted.as_str(), theme.dim).to_string()
+            });
+        }
+        if self.columns.tty {
+            let tty_formatted = format!("{:<7}", self.tty.as_deref().unwrap_or("-"));
+            parts.push(paint(tty_formatted.as_str(), theme.dim).to_string());
+        }
+        if self.columns.cpu_time {
+            let time_formatted = format!("{:>9}", optional_cpu_time(self.cpu_time));
+            parts.push(paint(time_formatted.as_str(), theme.dim).to_string());
+        }
+        if self.columns.sockets {
+            let sockets_formatted =
+                format!("{:>width$}", optional_count(self.socket_count), width = SOCKETS_WIDTH);
+            parts.push(paint(sockets_formatted.as_str(), theme.dim).to_string());
+        }
+        if self.columns.fd_usage {
+            let fd_formatted = format!("{:>6}", fd_usage_label(self.fd_usage));
+            parts.push(if self.fd_usage.is_some_and(|pct| pct >= FD_USAGE_WARN) {
+                paint(fd_formatted.as_str(), theme.hot).to_string()
+            } else {
+                paint(fd_formatted.as_str(), theme.dim).to_string()
+            });
+        }
+        if self.columns.prio {
+            let prio_formatted = format!("{:>4}", optional_nice(self.nice));
+            parts.push(paint(prio_formatted.as_str(), theme.dim).to_string());
+        }
+        if self.columns.owner {
+            let owner_formatted = format!("{:<width$}", owner_label(self), width = OWNER_WIDTH);
+            parts.push(paint(owner_formatted.as_str(), theme.dim).to_string());
+        }
+
+        write!(f, "{}", parts.join(self.columns.settings.gap))
     }
 }
 
-fn get_processes(filter: Option<&str>, sort_by: SortBy) -> Vec<ProcessInfo> {
-    let mut sys = System::new_all();
-    sys.refresh_all();
-    thread::sleep(Duration::from_millis(200));
-    sys.refresh_all();
+fn display_label(proc: &ProcessInfo) -> String {
+    let name = if proc.duplicates > 1 {
+        format!("{} (x{})", proc.name, proc.duplicates)
+    } else {
+        proc.name.clone()
+    };
+    let name = if proc.pinned {
+        format!("★ {}", name)
+    } else {
+        name
+    };
+    if proc.is_self {
+        format!("{} (this is rip)", name)
+    } else if proc.is_shell {
+        format!("{} (your shell)", name)
+    } else {
+        name
+    }
+}
 
-    let name_width = calculate_name_width(false);
+fn own_pids() -> HashSet<u32> {
+    let mut pids = HashSet::from([std::process::id()]);
+    // Threads show up as separate entries on Linux and signalling one hits the whole process.
+    if let Ok(tasks) = std::fs::read_dir("/proc/self/task") {
+        pids.extend(
+            tasks
+                .flatten()
+                .filter_map(|task| task.file_name().to_str()?.parse::<u32>().ok()),
+        );
+    }
+    pids
+}
 
-    let mut processes: Vec<ProcessInfo> = sys
-        .processes()
-        .iter()
-        .filter_map(|(pid, proc)| {
-            let name = proc.name().to_string_lossy().to_string();
+fn mark_self(processes: &mut [ProcessInfo]) {
+    let own = own_pids();
+    for proc in processes.iter_mut() {
+        proc.is_self = own.contains(&proc.pid);
+    }
+}
 
-            if let Some(f) = filter {
-                if !name.to_lowercase().contains(&f.to_lowercase()) {
-                    return None;
-                }
-            }
+// Walks parent links from `start` (exclusive) and stops after the session leader,
+// at init, or on a loop in the parent data.
+fn ancestor_chain(
+    parent_of: impl Fn(u32) -> Option<u32>,
+    start: u32,
+    leader: Option<u32>,
+) -> Vec<u32> {
+    let mut chain = Vec::new();
+    let mut current = start;
+    while let Some(parent) = parent_of(current) {
+        if parent <= 1 || parent == start || chain.contains(&parent) {
+            break;
+        }
+        chain.push(parent);
+        if Some(parent) == leader {
+            break;
+        }
+        current = parent;
+    }
+    chain
+}
 
-            Some(ProcessInfo {
-                pid: pid.as_u32(),
-                name,
-                cpu: proc.cpu_usage(),
-                memory: proc.memory() / 1024 / 1024,
-                name_width,
-                port: None,
-                protocol: None,
-            })
-        })
-        .collect();
+fn shell_pids(root: &Path) -> HashSet<u32> {
+    let Some(own) = linux_stat(root, std::process::id()) else {
+        return HashSet::new();
+    };
+    let parent_of = |pid: u32| {
+        if pid == own.pid {
+            Some(own.ppid)
+        } else {
+            linux_stat(root, pid).map(|stat| stat.ppid)
+        }
+    };
+    ancestor_chain(parent_of, own.pid, Some(own.session))
+        .into_iter()
+        .collect()
+}
 
-    sort_processes(&mut processes, sort_by);
-    processes
+fn mark_shell(processes: &mut [ProcessInfo], shell: &HashSet<u32>) {
+    for proc in processes.iter_mut() {
+        proc.is_shell = shell.contains(&proc.pid);
+    }
 }
 
-fn sort_processes(processes: &mut Vec<ProcessInfo>, sort_by: SortBy) {
-    match sort_by {
-        SortBy::Cpu => processes.sort_by(|a, b| b.cpu.partial_cmp(&a.cpu).unwrap()),
-        SortBy::Mem => processes.sort_by(|a, b| b.memory.cmp(&a.memory)),
-        SortBy::Pid => processes.sort_by(|a, b| a.pid.cmp(&b.pid)),
-        SortBy::Name => processes.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase())),
-        SortBy::Port => processes.sort_by(|a, b| a.port.cmp(&b.port)),
+fn guard_self(selected: Vec<ProcessInfo>) -> Vec<ProcessInfo> {
+    let (own, rest): (Vec<ProcessInfo>, Vec<ProcessInfo>) =
+        selected.into_iter().partition(|p| p.is_self || p.is_shell);
+    for proc in own {
+        let reason = if proc.is_self {
+            "this is rip; use --include-self"
+        } else {
+            "this is your shell; use --force"
+        };
+        eprintln!(
+            "{} {} {}",
+            Colorize::yellow("Skipped"),
+            Colorize::bold(proc.name.as_str()),
+            Colorize::dimmed(format!("(PID: {}, {})", proc.pid, reason).as_str())
+        );
     }
+    rest
 }
 
-fn get_port_mappings() -> HashMap<u32, Vec<(u16, String)>> {
-    let mut map: HashMap<u32, Vec<(u16, String)>> = HashMap::new();
+// Linux keeps only the first 15 bytes of the executable name in comm.
+const COMM_LEN: usize = 15;
 
-    if let Ok(listeners) = listeners::get_all() {
-        for listener in listeners {
-            let port = listener.socket.port();
-            let protocol = format!("{:?}", listener.protocol).to_uppercase();
-            let entry = map.entry(listener.process.pid).or_default();
-            if !entry.iter().any(|(p, proto)| *p == port && proto == &protocol) {
-                entry.push((port, protocol));
+// When comm looks cut off, prefer the executable or argv[0] file name it is a
+// prefix of; interpreters and renamed processes keep their comm.
+fn resolve_name(comm: &str, exe: Option<&Path>, argv0: Option<&str>) -> String {
+    if comm.len() != COMM_LEN {
+        return comm.to_string();
+    }
+    [exe, argv0.map(Path::new)]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| path.file_name()?.to_str())
+        .find(|candidate| candidate.len() > comm.len() && candidate.starts_with(comm))
+        .map_or_else(|| comm.to_string(), str::to_string)
+}
+
+fn process_name(proc: &Process) -> String {
+    let comm = proc.name().to_string_lossy();
+    let argv0 = proc.cmd().first().map(|arg| arg.to_string_lossy());
+    resolve_name(&comm, proc.exe(), argv0.as_deref())
+}
+
+fn command_line(proc: &Process) -> Vec<String> {
+    proc.cmd()
+        .iter()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect()
+}
+
+fn parse_cmdline(raw: &[u8]) -> Vec<String> {
+    raw.split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect()
+}
+
+fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
             }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        let current_len = current.chars().count();
+        if current_len > 0 && current_len + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
         }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
     }
+    lines
+}
 
-    map
+fn resolve_owner(proc: &Process, users: &Users) -> Option<String> {
+    let uid = proc.user_id()?;
+    match users.get_user_by_id(uid) {
+        Some(user) => Some(user.name().to_string()),
+        None => Some(uid.to_string()),
+    }
 }
 
-fn get_processes_with_ports(
-    filter: Option<&str>,
-    port_filter: Option<u16>,
-    sort_by: SortBy,
-) -> Vec<ProcessInfo> {
-    let mut sys = System::new_all();
-    sys.refresh_all();
-    thread::sleep(Duration::from_millis(200));
-    sys.refresh_all();
+fn owner_for_uid(uid: &str, users: &Users) -> String {
+    match uid.parse::<sysinfo::Uid>() {
+        Ok(parsed) => users
+            .get_user_by_id(&parsed)
+            .map(|u| u.name().to_string())
+            .unwrap_or_else(|| uid.to_string()),
+        Err(_) => uid.to_string(),
+    }
+}
 
-    let port_map = get_port_mappings();
-    let name_width = calculate_name_width(true);
+fn current_uid() -> Option<u32> {
+    #[cfg(unix)]
+    {
+        Some(nix::unistd::geteuid().as_raw())
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
 
-    let mut processes: Vec<ProcessInfo> = sys
-        .processes()
+// Owners are kept as user names, or the bare UID when it has no account, so
+// --user is resolved to that same label once before sampling.
+fn resolve_user(spec: &str, users: &Users) -> Result<String, String> {
+    if spec == "me" {
+        return current_uid()
+            .map(|uid| owner_for_uid(&uid.to_string(), users))
+            .ok_or_else(|| "Error: --user me is not supported on this platform".to_string());
+    }
+    if spec.chars().all(|c| c.is_ascii_digit()) && !spec.is_empty() {
+        return Ok(owner_for_uid(spec, users));
+    }
+    users
+        .list()
         .iter()
-        .flat_map(|(pid, proc)| {
-            let pid_u32 = pid.as_u32();
-            let ports = match port_map.get(&pid_u32) {
-                Some(p) => p,
-                None => return vec![],
-            };
-
-            let name = proc.name().to_string_lossy().to_string();
+        .find(|u| u.name() == spec)
+        .map(|u| u.name().to_string())
+        .ok_or_else(|| format!("Error: no such user: {}", spec))
+}
 
-            if let Some(f) = filter {
-                if !name.to_lowercase().contains(&f.to_lowercase()) {
-                    return vec![];
-                }
-            }
+fn parent_map(sys: &System) -> HashMap<u32, u32> {
+    sys.processes()
+        .iter()
+        .filter(|(_, proc)| proc.thread_kind() != Some(ThreadKind::Userland))
+        .filter_map(|(pid, proc)| Some((pid.as_u32(), proc.parent()?.as_u32())))
+        .collect()
+}
 
-            let cpu = proc.cpu_usage();
-            let memory = proc.memory() / 1024 / 1024;
+fn child_counts(parents: &HashMap<u32, u32>) -> HashMap<u32, usize> {
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for parent in parents.values() {
+        *counts.entry(*parent).or_default() += 1;
+    }
+    counts
+}
 
-            ports
-                .iter()
-                .filter_map(|(port, protocol)| {
-                    if let Some(target_port) = port_filter {
-                        if *port != target_port {
-                            return None;
-                        }
-                    }
+// Reorders processes depth-first so children follow their parent, keeping the
+// current sort among siblings (or start order with `spawn_order`). Processes
+// whose parent isn't in the list become roots.
+fn tree_order(processes: Vec<ProcessInfo>, spawn_order: bool) -> Vec<ProcessInfo> {
+    let index: HashMap<u32, usize> = processes.iter().enumerate().map(|(i, p)| (p.pid, i)).collect();
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut roots = Vec::new();
+    for (i, proc) in processes.iter().enumerate() {
+        match proc.parent.and_then(|ppid| index.get(&ppid)) {
+            Some(&parent) if parent != i => children.entry(parent).or_default().push(i),
+            _ => roots.push(i),
+        }
+    }
+    if spawn_order {
+        let by_start = |a: &usize, b: &usize| {
+            let (a, b) = (&processes[*a], &processes[*b]);
+            (a.start_time, a.pid).cmp(&(b.start_time, b.pid))
+        };
+        roots.sort_by(by_start);
+        for siblings in children.values_mut() {
+            siblings.sort_by(by_start);
+        }
+    }
 
-                    Some(ProcessInfo {
-                        pid: pid_u32,
-                        name: name.clone(),
-                        cpu,
-                        memory,
-                        name_width,
-                        port: Some(*port),
-                        protocol: Some(protocol.clone()),
-                    })
-                })
-                .collect::<Vec<_>>()
-        })
+    let mut order: Vec<(usize, String)> = Vec::with_capacity(processes.len());
+    let mut visited = HashSet::new();
+    let mut stack: Vec<(usize, String, String)> = roots
+        .iter()
+        .rev()
+        .map(|&i| (i, String::new(), String::new()))
         .collect();
+    while let Some((i, prefix, indent)) = stack.pop() {
+        if !visited.insert(i) {
+            continue;
+        }
+        order.push((i, prefix));
+        let kids = children.get(&i).map(Vec::as_slice).unwrap_or_default();
+        for (n, &child) in kids.iter().enumerate().rev() {
+            let last = n + 1 == kids.len();
+            let mut prefix = format!("{}{}", indent, if last { "└─ " } else { "├─ " });
+            if spawn_order {
+                prefix.push_str(&format!("{}. ", n + 1));
+            }
+            let child_indent = format!("{}{}", indent, if last { "   " } else { "│  " });
+            stack.push((child, prefix, child_indent));
+        }
+    }
+    // Parent cycles (PID reuse races) leave nodes unreached; list them as roots.
+    for i in 0..processes.len() {
+        if !visited.contains(&i) {
+            order.push((i, String::new()));
+        }
+    }
 
-    sort_processes(&mut processes, sort_by);
+    let mut slots: Vec<Option<ProcessInfo>> = processes.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .filter_map(|(i, prefix)| {
+            let mut proc = slots[i].take()?;
+            proc.tree_prefix = prefix;
+            Some(proc)
+        })
+        .collect()
+}
+
+// Folds thread rows into their process. Linux already counts every thread in
+// the process's own figure, so the larger of that and the thread sum is kept
+// rather than adding the two together.
+fn merge_thread_cpu(processes: Vec<ProcessInfo>) -> Vec<ProcessInfo> {
+    let mut thread_cpu: HashMap<u32, f32> = HashMap::new();
+    for proc in &processes {
+        if let Some(owner) = proc.thread_of {
+            *thread_cpu.entry(owner).or_default() += proc.cpu;
+        }
+    }
+    let owners: HashSet<u32> = processes
+        .iter()
+        .filter(|p| p.thread_of.is_none())
+        .map(|p| p.pid)
+        .collect();
     processes
+        .into_iter()
+        .filter(|p| p.thread_of.is_none_or(|owner| !owners.contains(&owner)))
+        .map(|mut p| {
+            if let Some(sum) = thread_cpu.get(&p.pid) {
+                p.cpu = p.cpu.max(*sum);
+            }
+            p
+        })
+        .collect()
 }
 
-fn parse_signal(signal_str: &str) -> Result<Signal, String> {
-    let signal_str = signal_str.to_uppercase();
-    let signal_str = signal_str.strip_prefix("SIG").unwrap_or(&signal_str);
+// Keeps each name at the position it first appears in the sorted list, with the
+// stats of its highest-CPU instance.
+fn dedupe_by_name(processes: Vec<ProcessInfo>) -> Vec<ProcessInfo> {
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut out: Vec<ProcessInfo> = Vec::new();
+    for proc in processes {
+        match index.get(&proc.name) {
+            Some(&i) => {
+                let count = out[i].duplicates + 1;
+                if proc.cpu > out[i].cpu {
+                    out[i] = proc;
+                }
+                out[i].duplicates = count;
+            }
+            None => {
+                index.insert(proc.name.clone(), out.len());
+                out.push(ProcessInfo { duplicates: 1, ..proc });
+            }
+        }
+    }
+    out
+}
 
-    #[cfg(unix)]
-    {
-        match signal_str {
-            "KILL" | "9" => Ok(Signal::SIGKILL),
-            "TERM" | "15" => Ok(Signal::SIGTERM),
-            "INT" | "2" => Ok(Signal::SIGINT),
-            "HUP" | "1" => Ok(Signal::SIGHUP),
-            "QUIT" | "3" => Ok(Signal::SIGQUIT),
-            "USR1" | "10" => Ok(Signal::SIGUSR1),
-            "USR2" | "12" => Ok(Signal::SIGUSR2),
-            "STOP" | "19" => Ok(Signal::SIGSTOP),
-            "CONT" | "18" => Ok(Signal::SIGCONT),
-            _ => Err(format!("Unknown signal: {}", signal_str)),
+// Remembers the last refresh at which each PID used any CPU, forgetting PIDs
+// that are gone.
+fn record_activity(processes: &mut [ProcessInfo], seen: &mut HashMap<u32, u64>, tick: u64) {
+    let live: HashSet<u32> = processes.iter().map(|p| p.pid).collect();
+    seen.retain(|pid, _| live.contains(pid));
+    for proc in processes.iter_mut() {
+        if proc.cpu > 0.0 {
+            seen.insert(proc.pid, tick);
         }
+        proc.last_active = seen.get(&proc.pid).copied();
     }
+}
 
-    #[cfg(windows)]
-    {
-        match signal_str {
-            "KILL" | "9" => Ok(Signal::Kill),
-            "TERM" | "15" => Ok(Signal::Term),
-            _ => Err(format!("Only KILL and TERM are supported on Windows: {}", signal_str)),
+// Rows shown in the live tree: anything below a collapsed PID is hidden. Relies on
+// tree order, where every process comes after its parent.
+fn visible_rows(processes: &[ProcessInfo], collapsed: &HashSet<u32>) -> Vec<usize> {
+    let mut hidden: HashSet<u32> = HashSet::new();
+    let mut visible = Vec::with_capacity(processes.len());
+    for (i, proc) in processes.iter().enumerate() {
+        if proc
+            .parent
+            .is_some_and(|ppid| collapsed.contains(&ppid) || hidden.contains(&ppid))
+        {
+            hidden.insert(proc.pid);
+        } else {
+            visible.push(i);
         }
     }
+    visible
 }
 
-fn run_selector(processes: Vec<ProcessInfo>, ports_mode: bool) -> Vec<ProcessInfo> {
-    if processes.is_empty() {
-        return vec![];
+fn single_protocol(processes: &[ProcessInfo]) -> Option<&'static str> {
+    let mut protocols = processes
+        .iter()
+        .filter(|p| p.port.is_some())
+        .map(|p| p.protocol.as_deref());
+    let first = protocols.next()?;
+    if protocols.any(|p| p != first) {
+        return None;
+    }
+    match first {
+        Some("TCP") => Some("TCP"),
+        Some("UDP") => Some("UDP"),
+        _ => None,
     }
+}
 
-    let name_width = calculate_name_width(ports_mode);
-    let pid_h = format!("{:<7}", "PID");
-    let name_h = format!("{:<width$}", "NAME", width = name_width);
-    let cpu_h = format!("{:>7}", "CPU %");
-    let mem_h = format!("{:>9}", "MEMORY");
-
-    let header = if ports_mode {
-        let port_h = format!("{:<9}", "PORT");
-        format!(
-            "    {} {} {} {} {}",
-            Colorize::dimmed(port_h.as_str()),
-            Colorize::dimmed(pid_h.as_str()),
-            Colorize::dimmed(name_h.as_str()),
-            Colorize::dimmed(cpu_h.as_str()),
-            Colorize::dimmed(mem_h.as_str()),
-        )
-    } else {
-        format!(
-            "    {} {} {} {}",
-            Colorize::dimmed(pid_h.as_str()),
-            Colorize::dimmed(name_h.as_str()),
-            Colorize::dimmed(cpu_h.as_str()),
-            Colorize::dimmed(mem_h.as_str()),
-        )
+fn port_header(columns: Columns) -> String {
+    match columns.single_protocol {
+        Some(proto) => format!("PORT/{}", proto),
+        None => "PORT".to_string(),
+    }
+}
+
+fn column_name_width(base: usize, columns: Columns) -> usize {
+    (base as isize - columns.extra_width()).max(15) as usize
+}
+
+// Live mode's table border, highlight symbol and selection marker column.
+const LIVE_CHROME_WIDTH: usize = 6;
+
+fn live_name_width(term_width: u16, columns: Columns, ports_mode: bool) -> usize {
+    let available = (term_width as usize).saturating_sub(LIVE_CHROME_WIDTH);
+    column_name_width(name_width_for(available, ports_mode), columns)
+}
+
+fn apply_columns(processes: &mut [ProcessInfo], columns: Columns, ports_mode: bool) {
+    let name_width = column_name_width(calculate_name_width(ports_mode), columns);
+    for proc in processes.iter_mut() {
+        proc.columns = columns;
+        proc.name_width = name_width;
+    }
+}
+
+fn sort_name(sort_by: SortBy) -> String {
+    sort_by
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn sort_descending(sort_by: SortBy) -> bool {
+    matches!(
+        sort_by,
+        SortBy::Cpu
+            | SortBy::Mem
+            | SortBy::Children
+            | SortBy::Args
+            | SortBy::LastActive
+            | SortBy::CpuTime
+            | SortBy::Sockets
+            | SortBy::FdPct
+    )
+}
+
+fn sort_header(label: &str, column: SortBy, sort_by: SortBy) -> String {
+    if column != sort_by {
+        return label.to_string();
+    }
+    let arrow = if sort_descending(sort_by) { "▼" } else { "▲" };
+    format!("{} {}", label, arrow)
+}
+
+fn next_sort(sort_by: SortBy, ports_mode: bool) -> SortBy {
+    let variants = SortBy::value_variants();
+    let start = variants.iter().position(|v| *v == sort_by).unwrap_or(0);
+    (1..=variants.len())
+        .map(|offset| variants[(start + offset) % variants.len()])
+        .find(|v| ports_mode || *v != SortBy::Port)
+        .unwrap_or(sort_by)
+}
+
+fn query_uses_cpu(query: &Query) -> bool {
+    match query {
+        Query::And(a, b) | Query::Or(a, b) => query_uses_cpu(a) || query_uses_cpu(b),
+        Query::Not(inner) => query_uses_cpu(inner),
+        Query::Number(field, _, _) | Query::Text(field, _, _) => *field == QueryField::Cpu,
+    }
+}
+
+// The one-shot path only pays for the 200ms CPU sample when something reads it:
+// a CPU-ordered list, a filter on it, or output that prints it.
+fn needs_cpu(args: &Args) -> bool {
+    let cpu_sort = |sort: SortBy| matches!(sort, SortBy::Cpu | SortBy::LastActive);
+    cpu_sort(args.sort)
+        || args.selector_sort.is_some_and(cpu_sort)
+        || args.summary
+        || args.sum_thread_cpu
+        || args.dedupe_by.is_some()
+        || args.output.is_some()
+        || args
+            .query
+            .as_deref()
+            .and_then(|q| parse_query(q).ok())
+            .is_some_and(|q| query_uses_cpu(&q))
+}
+
+fn top_shortcut(top_cpu: Option<usize>, top_mem: Option<usize>) -> Option<(SortBy, usize)> {
+    match (top_cpu, top_mem) {
+        (Some(n), _) => Some((SortBy::Cpu, n)),
+        (None, Some(n)) => Some((SortBy::Mem, n)),
+        (None, None) => None,
+    }
+}
+
+// cpu_usage() can come back NaN on the first sample, before there is a delta to
+// work from; rank it below every real value instead of panicking.
+fn cmp_cpu(a: f32, b: f32) -> std::cmp::Ordering {
+    let key = |cpu: f32| if cpu.is_nan() { f32::NEG_INFINITY } else { cpu };
+    key(a).total_cmp(&key(b))
+}
+
+fn sort_processes(processes: &mut [ProcessInfo], sort_by: SortBy) {
+    match sort_by {
+        // Idle processes all tie at 0.0%, so fall back to memory then PID to keep
+        // the tail of the live list from reshuffling on every refresh.
+        SortBy::Cpu => processes.sort_by(|a, b| {
+            cmp_cpu(b.cpu, a.cpu)
+                .then_with(|| b.memory.cmp(&a.memory))
+                .then_with(|| a.pid.cmp(&b.pid))
+        }),
+        SortBy::Mem => processes.sort_by_key(|p| std::cmp::Reverse(p.memory)),
+        SortBy::Pid => processes.sort_by_key(|p| p.pid),
+        SortBy::Name => processes.sort_by_key(|p| p.name.to_lowercase()),
+        SortBy::Port => processes.sort_by_key(|p| p.port),
+        SortBy::Children => processes.sort_by_key(|p| std::cmp::Reverse(p.child_count)),
+        SortBy::Args => processes.sort_by_key(|p| std::cmp::Reverse(p.cmd.len())),
+        // Never-active rows (None) sort last; outside live mode nothing is
+        // tracked and this degrades to a CPU sort.
+        SortBy::LastActive => processes.sort_by(|a, b| {
+            b.last_active
+                .cmp(&a.last_active)
+                .then_with(|| cmp_cpu(b.cpu, a.cpu))
+                .then_with(|| a.pid.cmp(&b.pid))
+        }),
+        // CPU time and socket counts are only filled in after fetching, so callers
+        // re-sort once they are (see sorts_after_enrich).
+        SortBy::CpuTime => processes.sort_by(|a, b| {
+            b.cpu_time
+                .cmp(&a.cpu_time)
+                .then_with(|| a.pid.cmp(&b.pid))
+        }),
+        SortBy::Sockets => processes.sort_by(|a, b| {
+            b.socket_count
+                .cmp(&a.socket_count)
+                .then_with(|| a.pid.cmp(&b.pid))
+        }),
+        SortBy::FdPct => processes.sort_by(|a, b| {
+            let pct = |p: &ProcessInfo| p.fd_usage.unwrap_or(-1.0);
+            pct(b).total_cmp(&pct(a)).then_with(|| a.pid.cmp(&b.pid))
+        }),
+    }
+    // Stable, so pinned rows keep the chosen order among themselves.
+    processes.sort_by_key(|p| !p.pinned);
+}
+
+fn mark_pinned(processes: &mut [ProcessInfo], patterns: &[String]) {
+    let patterns: Vec<String> = patterns.iter().map(|p| p.to_lowercase()).collect();
+    for proc in processes.iter_mut() {
+        let name = proc.name.to_lowercase();
+        proc.pinned = patterns.iter().any(|p| name.contains(p.as_str()));
+    }
+}
+
+// xorshift64 Fisher-Yates; good enough to scramble rows and avoids a rand dependency.
+fn shuffle_processes(processes: &mut [ProcessInfo], seed: u64) {
+    let mut state = seed | 1;
+    for i in (1..processes.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        processes.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+}
+
+fn shuffle_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ConfigValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    Array(Vec<ConfigValue>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Config {
+    danger_ports: Vec<u16>,
+    sort: Option<SortBy>,
+    safe: bool,
+    signal_rules: Vec<(String, Signal)>,
+    profiles: HashMap<String, Vec<(String, ConfigValue)>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            danger_ports: vec![22],
+            sort: None,
+            safe: false,
+            signal_rules: Vec::new(),
+            profiles: HashMap::new(),
+        }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("RIP_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
     };
+    Some(base.join("rip").join("config.toml"))
+}
 
-    let ans = MultiSelect::new(&format!("{}\n", header), processes)
-        .with_page_size(15)
-        .with_help_message("↑↓ navigate • Space select • Enter confirm • Type to filter")
-        .prompt();
+fn parse_config_value(raw: &str) -> Result<ConfigValue, String> {
+    let raw = raw.trim();
+    if let Some(inner) = raw.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        return inner
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(parse_config_value)
+            .collect::<Result<Vec<_>, _>>()
+            .map(ConfigValue::Array);
+    }
+    if let Some(inner) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) {
+        return Ok(ConfigValue::Str(inner.to_string()));
+    }
+    match raw {
+        "true" => return Ok(ConfigValue::Bool(true)),
+        "false" => return Ok(ConfigValue::Bool(false)),
+        _ => {}
+    }
+    raw.parse()
+        .map(ConfigValue::Int)
+        .map_err(|_| format!("invalid value: {}", raw))
+}
 
-    match ans {
-        Ok(selected) => selected,
-        Err(_) => vec![],
+// Parses the small TOML subset rip uses: `[section]` headers and `key = value`
+// lines with string, integer, boolean and flat array values.
+fn parse_config_entries(text: &str) -> Result<Vec<(String, String, ConfigValue)>, String> {
+    let mut section = String::new();
+    let mut entries = Vec::new();
+
+    for (lineno, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected `key = value`", lineno + 1))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let value = parse_config_value(value).map_err(|e| format!("line {}: {}", lineno + 1, e))?;
+        entries.push((section.clone(), key, value));
     }
+
+    Ok(entries)
 }
 
-fn run_live_mode(
-    filter: Option<&str>,
-    sort_by: SortBy,
-    signal: Signal,
-    ports_mode: bool,
-    port_filter: Option<u16>,
-) -> std::io::Result<()> {
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+fn parse_config(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
 
-    let mut table_state = TableState::default();
-    table_state.select(Some(0));
-    let mut selected_pids: HashSet<u32> = HashSet::new();
-    let mut last_refresh = Instant::now();
-    let refresh_interval = Duration::from_secs(2);
-    let mut sys = System::new_all();
-    let mut processes = if ports_mode {
-        refresh_processes_with_ports(&mut sys, filter, port_filter, sort_by)
-    } else {
-        refresh_processes(&mut sys, filter, sort_by)
+    for (section, key, value) in parse_config_entries(text)? {
+        match (section.as_str(), key.as_str(), value) {
+            ("", "danger_ports", ConfigValue::Array(items)) => {
+                config.danger_ports = items
+                    .into_iter()
+                    .map(|item| match item {
+                        ConfigValue::Int(port) => u16::try_from(port)
+                            .map_err(|_| format!("danger_ports: invalid port {}", port)),
+                        other => Err(format!("danger_ports: invalid port {:?}", other)),
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            ("", "sort", ConfigValue::Str(name)) => {
+                config.sort = Some(
+                    SortBy::from_str(&name, true).map_err(|_| format!("sort: unknown field {}", name))?,
+                );
+            }
+            ("", "safe", ConfigValue::Bool(safe)) => {
+                config.safe = safe;
+            }
+            ("signals", pattern, ConfigValue::Str(name)) => {
+                let signal = parse_signal(&name).map_err(|e| format!("signals.{}: {}", pattern, e))?;
+                config.signal_rules.push((pattern.to_lowercase(), signal));
+            }
+            (section, key, value) if section.starts_with("profiles.") => {
+                let name = &section["profiles.".len()..];
+                config
+                    .profiles
+                    .entry(name.to_string())
+                    .or_default()
+                    .push((key.to_string(), value));
+            }
+            (section, key, _) => {
+                let name = if section.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", section, key)
+                };
+                return Err(format!("unknown or invalid setting: {}", name));
+            }
+        }
+    }
+
+    Ok(config)
+}
+
+fn load_config() -> Result<Config, String> {
+    let path = match config_path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(Config::default()),
     };
-    let mut show_confirm = false;
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Error: failed to read {}: {}", path.display(), e))?;
+    parse_config(&text).map_err(|e| format!("Error: {}: {}", path.display(), e))
+}
 
-    loop {
-        if last_refresh.elapsed() >= refresh_interval && !show_confirm {
-            processes = if ports_mode {
-                refresh_processes_with_ports(&mut sys, filter, port_filter, sort_by)
-            } else {
-                refresh_processes(&mut sys, filter, sort_by)
-            };
-            last_refresh = Instant::now();
-            if let Some(selected) = table_state.selected() {
-                if selected >= processes.len() && !processes.is_empty() {
-                    table_state.select(Some(processes.len() - 1));
-                }
+// Profile keys are long flag names (`show_tty` or `show-tty`); they go in front of
+// the real arguments so that anything given on the command line overrides them.
+fn profile_args(entries: &[(String, ConfigValue)]) -> Vec<String> {
+    let mut out = Vec::new();
+    for (key, value) in entries {
+        let flag = format!("--{}", key.replace('_', "-"));
+        let values = match value {
+            ConfigValue::Array(items) => items.clone(),
+            other => vec![other.clone()],
+        };
+        for value in values {
+            match value {
+                ConfigValue::Bool(true) => out.push(flag.clone()),
+                ConfigValue::Bool(false) => {}
+                ConfigValue::Str(v) => out.extend([flag.clone(), v]),
+                ConfigValue::Int(v) => out.extend([flag.clone(), v.to_string()]),
+                ConfigValue::Array(_) => {}
             }
         }
+    }
+    out
+}
 
-        terminal.draw(|frame| {
-            let area = frame.area();
-            let rows: Vec<Row> = processes
-                .iter()
-                .map(|p| {
-                    let is_selected = selected_pids.contains(&p.pid);
-                    let marker = if is_selected { "●" } else { " " };
-                    let cpu_style = if p.cpu > 50.0 {
-                        Style::default().fg(Color::Red).bold()
-                    } else if p.cpu > 10.0 {
-                        Style::default().fg(Color::Yellow)
-                    } else {
-                        Style::default().fg(Color::DarkGray)
-                    };
+fn profile_argv(
+    config: &Config,
+    name: &str,
+    argv: Vec<std::ffi::OsString>,
+) -> Result<Vec<std::ffi::OsString>, String> {
+    let entries = config
+        .profiles
+        .get(name)
+        .ok_or_else(|| format!("Error: unknown profile {}", name))?;
+    let mut argv = argv.into_iter();
+    let mut merged: Vec<std::ffi::OsString> = argv.next().into_iter().collect();
+    merged.extend(profile_args(entries).into_iter().map(Into::into));
+    merged.extend(argv);
+    Ok(merged)
+}
 
-                    let mut cells = vec![
-                        Cell::from(marker).style(if is_selected {
-                            Style::default().fg(Color::Green).bold()
-                        } else {
-                            Style::default()
-                        }),
-                    ];
+// Replaces a top-level `key = value` line, or adds one above the first section,
+// leaving comments and the rest of the file untouched.
+fn set_config_value(text: &str, key: &str, value: &str) -> String {
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let new_line = format!("{} = {}", key, value);
+    let top_level_end = lines
+        .iter()
+        .position(|l| l.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    let existing = lines[..top_level_end].iter().position(|l| {
+        l.split_once('=')
+            .is_some_and(|(k, _)| k.trim().trim_matches('"') == key)
+    });
 
-                    if ports_mode {
-                        let port_str = p
-                            .port
-                            .map(|port| format!("{:<5}", port))
-                            .unwrap_or_default();
-                        let proto_str = p.protocol.as_deref().unwrap_or("");
-                        cells.push(
-                            Cell::from(format!("{} {:>3}", port_str, proto_str))
-                                .style(Style::default().fg(Color::Green)),
-                        );
-                    }
+    match existing {
+        Some(i) => lines[i] = new_line,
+        None => lines.insert(top_level_end, new_line),
+    }
 
-                    cells.extend([
-                        Cell::from(format!("{:<7}", p.pid))
-                            .style(Style::default().fg(Color::DarkGray)),
-                        Cell::from(truncate(&p.name, 40)).style(Style::default().fg(Color::White)),
-                        Cell::from(format!("{:>6.1}%", p.cpu)).style(cpu_style),
-                        Cell::from(format!("{:>6} MB", p.memory))
-                            .style(Style::default().fg(Color::Cyan)),
-                    ]);
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
 
-                    Row::new(cells)
-                })
-                .collect();
+fn save_default_sort(sort_by: SortBy) -> Result<PathBuf, String> {
+    let path = config_path().ok_or("no config directory")?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.to_string()),
+    };
+    let updated = set_config_value(&text, "sort", &format!("\"{}\"", sort_name(sort_by)));
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&path, updated).map_err(|e| e.to_string())?;
+    Ok(path)
+}
 
-            let (header, widths): (Row, Vec<Constraint>) = if ports_mode {
-                (
-                    Row::new(vec![
-                        Cell::from(" "),
-                        Cell::from(format!("{:<9}", "PORT"))
-                            .style(Style::default().fg(Color::DarkGray)),
-                        Cell::from(format!("{:<7}", "PID"))
-                            .style(Style::default().fg(Color::DarkGray)),
-                        Cell::from("NAME").style(Style::default().fg(Color::DarkGray)),
-                        Cell::from(format!("{:>7}", "CPU %"))
-                            .style(Style::default().fg(Color::DarkGray)),
-                        Cell::from(format!("{:>9}", "MEMORY"))
-                            .style(Style::default().fg(Color::DarkGray)),
-                    ])
-                    .style(Style::default().bold()),
-                    vec![
-                        Constraint::Length(2),
-                        Constraint::Length(9),
-                        Constraint::Length(7),
-                        Constraint::Min(20),
-                        Constraint::Length(7),
-                        Constraint::Length(9),
-                    ],
-                )
-            } else {
-                (
-                    Row::new(vec![
-                        Cell::from(" "),
-                        Cell::from(format!("{:<7}", "PID"))
-                            .style(Style::default().fg(Color::DarkGray)),
-                        Cell::from("NAME").style(Style::default().fg(Color::DarkGray)),
-                        Cell::from(format!("{:>7}", "CPU %"))
-                            .style(Style::default().fg(Color::DarkGray)),
-                        Cell::from(format!("{:>9}", "MEMORY"))
-                            .style(Style::default().fg(Color::DarkGray)),
-                    ])
-                    .style(Style::default().bold()),
-                    vec![
-                        Constraint::Length(2),
-                        Constraint::Length(7),
-                        Constraint::Min(20),
-                        Constraint::Length(7),
-                        Constraint::Length(9),
-                    ],
-                )
-            };
+fn mark_danger(processes: &mut [ProcessInfo], danger_ports: &[u16]) {
+    for proc in processes.iter_mut() {
+        proc.danger = proc.port.is_some_and(|port| danger_ports.contains(&port));
+    }
+}
 
-            let selected_count = selected_pids.len();
-            let title = if selected_count > 0 {
-                format!(" rip - {} selected ", selected_count)
-            } else {
-                " rip ".to_string()
-            };
+fn danger_ports_in(selected: &[ProcessInfo]) -> Vec<u16> {
+    let mut ports: Vec<u16> = selected
+        .iter()
+        .filter(|p| p.danger)
+        .filter_map(|p| p.port)
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
 
-            let table = Table::new(rows, widths)
-                .header(header)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title(title)
-                        .title_bottom(" ↑↓ navigate • Space select • Enter kill • q quit "),
-                )
-                .row_highlight_style(Style::default().bg(Color::DarkGray).fg(Color::White))
-                .highlight_symbol("▶ ");
+fn confirm_danger(settings: &Settings, selected: &[ProcessInfo]) -> bool {
+    let ports = danger_ports_in(selected);
+    if ports.is_empty() {
+        return true;
+    }
+    let list: Vec<String> = ports.iter().map(|p| p.to_string()).collect();
+    ask_confirm(
+        settings,
+        &format!(
+            "Selection includes processes on danger port(s) {}. Kill anyway?",
+            list.join(", ")
+        ),
+        false,
+    )
+}
 
-            frame.render_stateful_widget(table, area, &mut table_state);
+// Run-wide choices resolved once in main. Rows carry a copy in their Columns
+// so the selector can render them through Display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Settings {
+    proc_root: &'static Path,
+    mem_kind: MemKind,
+    gap: &'static str,
+    confirm_style: ConfirmStyle,
+    error_json: bool,
+    theme: Theme,
+}
 
-            if show_confirm {
-                let popup_area = centered_rect(50, 20, area);
-                frame.render_widget(Clear, popup_area);
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            proc_root: Path::new("/proc"),
+            mem_kind: MemKind::default(),
+            gap: " ",
+            confirm_style: ConfirmStyle::default(),
+            error_json: false,
+            theme: Theme::named(ThemeName::Dark),
+        }
+    }
+}
 
-                let count = selected_pids.len();
-                let text = format!(
-                    "Kill {} process{}?\n\n[Enter] Confirm  [Esc] Cancel",
-                    count,
-                    if count == 1 { "" } else { "es" }
-                );
-                let popup = Paragraph::new(text)
-                    .alignment(Alignment::Center)
-                    .block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .title(" Confirm ")
-                            .border_style(Style::default().fg(Color::Yellow)),
-                    );
-                frame.render_widget(popup, popup_area);
-            }
-        })?;
+// Roles rather than colors, so the list, the live table and the legend stay in
+// step whichever palette is active.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Theme {
+    dim: Color,
+    text: Color,
+    hot: Color,
+    warm: Color,
+    good: Color,
+    udp: Color,
+    accent: Color,
+    own: Color,
+    highlight: Color,
+}
 
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    if show_confirm {
-                        match key.code {
-                            KeyCode::Enter => {
-                                break;
-                            }
-                            KeyCode::Esc => {
-                                show_confirm = false;
-                            }
-                            _ => {}
-                        }
-                    } else {
-                        match key.code {
-                            KeyCode::Char('q') | KeyCode::Esc => {
-                                selected_pids.clear();
-                                break;
-                            }
-                            KeyCode::Up | KeyCode::Char('k') => {
-                                if let Some(selected) = table_state.selected() {
-                                    if selected > 0 {
-                                        table_state.select(Some(selected - 1));
-                                    }
-                                }
-                            }
-                            KeyCode::Down | KeyCode::Char('j') => {
-                                if let Some(selected) = table_state.selected() {
-                                    if selected < processes.len().saturating_sub(1) {
-                                        table_state.select(Some(selected + 1));
-                                    }
-                                }
-                            }
-                            KeyCode::Char(' ') => {
-                                if let Some(selected) = table_state.selected() {
-                                    if let Some(proc) = processes.get(selected) {
-                                        if selected_pids.contains(&proc.pid) {
-                                            selected_pids.remove(&proc.pid);
-                                        } else {
-                                            selected_pids.insert(proc.pid);
-                                        }
-                                    }
-                                }
-                            }
-                            KeyCode::Enter => {
-                                if !selected_pids.is_empty() {
-                                    show_confirm = true;
-                                }
-                            }
-                            _ => {}
-                        }
-                    }
+impl Theme {
+    fn named(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark => Theme {
+                dim: Color::DarkGray,
+                text: Color::White,
+                hot: Color::Red,
+                warm: Color::Yellow,
+                good: Color::Green,
+                udp: Color::Blue,
+                accent: Color::Cyan,
+                own: Color::Magenta,
+                highlight: Color::DarkGray,
+            },
+            // Yellow and white vanish on a light background.
+            ThemeName::Light => Theme {
+                dim: Color::DarkGray,
+                text: Color::Black,
+                hot: Color::Red,
+                warm: Color::Magenta,
+                good: Color::Green,
+                udp: Color::Blue,
+                accent: Color::Blue,
+                own: Color::Magenta,
+                highlight: Color::Gray,
+            },
+            // No dimming, and only the bright variants.
+            ThemeName::HighContrast => Theme {
+                dim: Color::White,
+                text: Color::White,
+                hot: Color::LightRed,
+                warm: Color::LightYellow,
+                good: Color::LightGreen,
+                udp: Color::LightBlue,
+                accent: Color::LightCyan,
+                own: Color::LightMagenta,
+                highlight: Color::Blue,
+            },
+            ThemeName::Mono => Theme {
+                dim: Color::Reset,
+                text: Color::Reset,
+                hot: Color::Reset,
+                warm: Color::Reset,
+                good: Color::Reset,
+                udp: Color::Reset,
+                accent: Color::Reset,
+                own: Color::Reset,
+                highlight: Color::Reset,
+            },
+        }
+    }
+
+    // Shared by the list and the live table so a port reads the same in both.
+    fn port(&self, proc: &ProcessInfo) -> Color {
+        if proc.danger {
+            self.hot
+        } else if is_udp(proc.protocol.as_deref()) {
+            self.udp
+        } else {
+            self.good
+        }
+    }
+}
+
+// The plain list goes through colored; DarkGray maps to its dimmed style so the
+// dark theme looks exactly as it always has.
+fn paint(text: &str, color: Color) -> colored::ColoredString {
+    let named = match color {
+        Color::Reset => return Colorize::normal(text),
+        Color::DarkGray => return Colorize::dimmed(text),
+        Color::Black => colored::Color::Black,
+        Color::Red => colored::Color::Red,
+        Color::Green => colored::Color::Green,
+        Color::Yellow => colored::Color::Yellow,
+        Color::Blue => colored::Color::Blue,
+        Color::Magenta => colored::Color::Magenta,
+        Color::Cyan => colored::Color::Cyan,
+        Color::Gray | Color::White => colored::Color::White,
+        Color::LightRed => colored::Color::BrightRed,
+        Color::LightGreen => colored::Color::BrightGreen,
+        Color::LightYellow => colored::Color::BrightYellow,
+        Color::LightBlue => colored::Color::BrightBlue,
+        Color::LightMagenta => colored::Color::BrightMagenta,
+        Color::LightCyan => colored::Color::BrightCyan,
+        Color::Rgb(r, g, b) => colored::Color::TrueColor { r, g, b },
+        Color::Indexed(_) => return Colorize::normal(text),
+    };
+    Colorize::color(text, named)
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn error_json(message: &str, code: &str) -> String {
+    let message = message.strip_prefix("Error: ").unwrap_or(message);
+    format!("{{\"error\":{},\"code\":{}}}", json_string(message), json_string(code))
+}
+
+fn report_error(settings: &Settings, message: &str, code: &str) {
+    if settings.error_json {
+        eprintln!("{}", error_json(message, code));
+    } else {
+        eprintln!("{}", Colorize::red(message));
+    }
+}
+
+const EXIT_ERROR: i32 = 1;
+const EXIT_NO_MATCH: i32 = 2;
+const EXIT_KILL_FAILED: i32 = 3;
+const EXIT_ABORTED: i32 = 4;
+
+fn fail(settings: &Settings, message: &str, code: &str) -> ! {
+    report_error(settings, message, code);
+    std::process::exit(EXIT_ERROR);
+}
+
+// clap exits with 2 on usage errors, which scripts would read as "nothing matched".
+fn clap_exit(e: clap::Error) -> ! {
+    let _ = e.print();
+    std::process::exit(if e.use_stderr() { EXIT_ERROR } else { 0 });
+}
+
+// An empty selection is a user abort unless everything the user picked was
+// dropped as unkillable (rip itself or the calling shell).
+fn empty_selection_code(picked: bool, killable: bool) -> i32 {
+    if picked && !killable {
+        EXIT_NO_MATCH
+    } else {
+        EXIT_ABORTED
+    }
+}
+
+// Ctrl-C during the kill loop counts as an abort; otherwise anything not
+// signalled failed.
+fn kill_exit_code(attempted: usize, signalled: usize, interrupted: bool) -> i32 {
+    if interrupted {
+        EXIT_ABORTED
+    } else if signalled < attempted {
+        EXIT_KILL_FAILED
+    } else {
+        0
+    }
+}
+
+// inquire redraws with cursor movement on stderr, which garbles dumb terminals
+// and logs, so fall back to a plain line unless both ends are real terminals.
+fn resolve_confirm_style(
+    flag: Option<ConfirmStyle>,
+    terminals: Terminals,
+    dumb: bool,
+) -> ConfirmStyle {
+    flag.unwrap_or(if terminals.stdin && terminals.stderr && !dumb {
+        ConfirmStyle::Fancy
+    } else {
+        ConfirmStyle::Plain
+    })
+}
+
+fn read_plain_answer(prompt: &str) -> Option<String> {
+    eprint!("{} ", prompt);
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    match std::io::stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer),
+    }
+}
+
+fn parse_yes(answer: &str, default: bool) -> bool {
+    match answer.trim().to_lowercase().as_str() {
+        "" => default,
+        "y" | "yes" => true,
+        _ => false,
+    }
+}
+
+fn ask_confirm(settings: &Settings, prompt: &str, default: bool) -> bool {
+    match settings.confirm_style {
+        ConfirmStyle::Fancy => Confirm::new(prompt)
+            .with_default(default)
+            .prompt()
+            .unwrap_or(false),
+        ConfirmStyle::Plain => {
+            let hint = if default { "[Y/n]" } else { "[y/N]" };
+            read_plain_answer(&format!("{} {}", prompt, hint))
+                .is_some_and(|a| parse_yes(&a, default))
+        }
+    }
+}
+
+fn column_gap(separator: &str, pad: usize) -> String {
+    let padding = " ".repeat(pad);
+    format!("{}{}{}", padding, separator, padding)
+}
+
+impl MemKind {
+    fn label(self) -> &'static str {
+        match self {
+            MemKind::Rss => "RSS",
+            MemKind::Virtual => "VIRT",
+        }
+    }
+
+    // The matching line in /proc/<pid>/status.
+    fn status_field(self) -> &'static str {
+        match self {
+            MemKind::Rss => "VmRSS",
+            MemKind::Virtual => "VmSize",
+        }
+    }
+}
+
+fn process_memory(proc: &Process, kind: MemKind) -> u64 {
+    match kind {
+        MemKind::Rss => proc.memory(),
+        MemKind::Virtual => proc.virtual_memory(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ProcStat {
+    pid: u32,
+    comm: String,
+    state: char,
+    ppid: u32,
+    pgrp: u32,
+    session: u32,
+    tty_nr: u32,
+    tpgid: i32,
+    utime: u64,
+    stime: u64,
+    nice: Option<i32>,
+    start_ticks: Option<u64>,
+}
+
+fn parse_proc_stat(line: &str) -> Option<ProcStat> {
+    let open = line.find('(')?;
+    let close = line.rfind(')')?;
+    let pid = line[..open].trim().parse().ok()?;
+    let comm = line[open + 1..close].to_string();
+    let mut rest = line[close + 1..].split_whitespace();
+    let state = rest.next()?.chars().next()?;
+    let ppid = rest.next()?.parse().ok()?;
+    let pgrp = rest.next()?.parse().ok()?;
+    let session = rest.next()?.parse().ok()?;
+    let tty_nr = rest.next()?.parse().ok()?;
+    let tpgid = rest.next()?.parse().ok()?;
+    let mut rest = rest.skip(5);
+    let utime = rest.next()?.parse().ok()?;
+    let stime = rest.next()?.parse().ok()?;
+    // cutime, cstime and the kernel priority come before nice.
+    let nice = rest.nth(3).and_then(|v| v.parse().ok());
+    // num_threads and itrealvalue sit between nice and the start time.
+    let start_ticks = rest.nth(2).and_then(|v| v.parse().ok());
+    Some(ProcStat {
+        pid,
+        comm,
+        state,
+        ppid,
+        pgrp,
+        session,
+        tty_nr,
+        tpgid,
+        utime,
+        stime,
+        nice,
+        start_ticks,
+    })
+}
+
+fn read_proc_stat(root: &Path, pid: u32) -> Option<ProcStat> {
+    let line = std::fs::read_to_string(root.join(pid.to_string()).join("stat")).ok()?;
+    parse_proc_stat(&line)
+}
+
+fn linux_stat(root: &Path, pid: u32) -> Option<ProcStat> {
+    if cfg!(target_os = "linux") {
+        read_proc_stat(root, pid)
+    } else {
+        None
+    }
+}
+
+// tty_nr packs the device number as minor bits 0-7 and 20-31, major bits 8-19.
+fn tty_name(tty_nr: u32) -> Option<String> {
+    if tty_nr == 0 {
+        return None;
+    }
+    let major = (tty_nr >> 8) & 0xfff;
+    let minor = (tty_nr & 0xff) | ((tty_nr >> 12) & 0xfff00);
+    Some(match major {
+        136..=143 => format!("pts/{}", (major - 136) * 256 + minor),
+        4 if minor < 64 => format!("tty{}", minor),
+        4 => format!("ttyS{}", minor - 64),
+        _ => format!("{}:{}", major, minor),
+    })
+}
+
+fn is_foreground(stat: &ProcStat) -> bool {
+    stat.tty_nr != 0 && stat.tpgid > 0 && stat.tpgid as u32 == stat.pgrp
+}
+
+// Processes whose niceness couldn't be read never match a range.
+fn nice_in_range(nice: Option<i32>, min: Option<i32>, max: Option<i32>) -> bool {
+    nice.is_some_and(|n| min.is_none_or(|min| n >= min) && max.is_none_or(|max| n <= max))
+}
+
+const OWNER_WIDTH: usize = 10;
+
+fn owner_label(proc: &ProcessInfo) -> String {
+    truncate(proc.owner.as_deref().unwrap_or("-"), OWNER_WIDTH)
+}
+
+fn optional_nice(nice: Option<i32>) -> String {
+    nice.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+fn foreground_label(foreground: Option<bool>) -> &'static str {
+    match foreground {
+        Some(true) => "fg",
+        Some(false) => "bg",
+        None => "-",
+    }
+}
+
+fn enrich_processes(processes: &mut [ProcessInfo], root: &Path) {
+    let mut stats: HashMap<u32, Option<ProcStat>> = HashMap::new();
+    let ticks_per_sec = clock_ticks_per_sec();
+    for proc in processes.iter_mut() {
+        let stat = stats.entry(proc.pid).or_insert_with(|| linux_stat(root, proc.pid));
+        if let Some(stat) = stat {
+            proc.session = Some(stat.session);
+            proc.foreground = Some(is_foreground(stat));
+            proc.cpu_ticks = Some(stat.utime + stat.stime);
+            proc.cpu_time = Some(cpu_seconds(stat.utime + stat.stime, ticks_per_sec));
+            proc.tty = tty_name(stat.tty_nr);
+            proc.nice = stat.nice;
+            proc.start_ticks = stat.start_ticks;
+        }
+    }
+}
+
+fn sorts_after_enrich(sort_by: SortBy) -> bool {
+    matches!(sort_by, SortBy::CpuTime | SortBy::Sockets | SortBy::FdPct)
+}
+
+fn cpu_seconds(ticks: u64, ticks_per_sec: f64) -> u64 {
+    (ticks as f64 / ticks_per_sec) as u64
+}
+
+fn format_cpu_time(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn optional_cpu_time(secs: Option<u64>) -> String {
+    secs.map(format_cpu_time).unwrap_or_else(|| "-".to_string())
+}
+
+fn current_session() -> Option<u32> {
+    #[cfg(target_os = "linux")]
+    {
+        nix::unistd::getsid(None)
+            .ok()
+            .map(|sid| sid.as_raw() as u32)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+fn session_matches(session: Option<u32>, target: u32) -> bool {
+    session == Some(target)
+}
+
+fn session_members(stats: &[(u32, u32)], sessions: &HashSet<u32>) -> Vec<u32> {
+    stats
+        .iter()
+        .filter(|(_, sid)| *sid != 0 && sessions.contains(sid))
+        .map(|(pid, _)| *pid)
+        .collect()
+}
+
+fn all_proc_stats(root: &Path) -> HashMap<u32, ProcStat> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return HashMap::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| Some((pid, read_proc_stat(root, pid)?)))
+        .collect()
+}
+
+// Adds the rest of each selected process's session and moves session leaders
+// to the end, so a login shell isn't hung up before the jobs it started.
+fn expand_sessions(selected: Vec<ProcessInfo>, root: &Path) -> Vec<ProcessInfo> {
+    let sessions: HashSet<u32> = selected
+        .iter()
+        .filter_map(|p| p.session)
+        .filter(|sid| *sid != 0)
+        .collect();
+    if sessions.is_empty() {
+        return selected;
+    }
+    let stats = all_proc_stats(root);
+    let pairs: Vec<(u32, u32)> = stats.values().map(|s| (s.pid, s.session)).collect();
+    let known: HashSet<u32> = selected.iter().map(|p| p.pid).collect();
+    let mut expanded = selected;
+    for pid in session_members(&pairs, &sessions) {
+        if known.contains(&pid) {
+            continue;
+        }
+        let stat = &stats[&pid];
+        expanded.push(ProcessInfo {
+            pid,
+            name: stat.comm.clone(),
+            session: Some(stat.session),
+            start_ticks: stat.start_ticks,
+            parent: Some(stat.ppid).filter(|ppid| *ppid != 0),
+            zombie: stat.state == 'Z',
+            ..Default::default()
+        });
+    }
+    expanded.sort_by_key(|p| p.session == Some(p.pid));
+    expanded
+}
+
+fn children_map(parents: &HashMap<u32, u32>) -> HashMap<u32, Vec<u32>> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (child, parent) in parents {
+        children.entry(*parent).or_default().push(*child);
+    }
+    for kids in children.values_mut() {
+        kids.sort_unstable();
+    }
+    children
+}
+
+// Post-order walk: every process comes after all of its descendants, so nothing
+// is signalled while a child it spawned is still waiting to be reparented. A root
+// that sits inside another root's subtree is only listed once.
+fn tree_kill_order(roots: &[u32], children: &HashMap<u32, Vec<u32>>) -> Vec<u32> {
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    for &root in roots {
+        if !seen.insert(root) {
+            continue;
+        }
+        let mut stack = vec![(root, false)];
+        while let Some((pid, expanded)) = stack.pop() {
+            if expanded {
+                order.push(pid);
+                continue;
+            }
+            stack.push((pid, true));
+            for &child in children.get(&pid).into_iter().flatten().rev() {
+                if seen.insert(child) {
+                    stack.push((child, false));
                 }
             }
         }
     }
+    order
+}
+
+fn expand_tree(selected: Vec<ProcessInfo>, root: &Path) -> Vec<ProcessInfo> {
+    let mut sys = System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+    let children = children_map(&parent_map(&sys));
+    let roots: Vec<u32> = selected.iter().map(|p| p.pid).collect();
+    let mut rows: HashMap<u32, Vec<ProcessInfo>> = HashMap::new();
+    for proc in selected {
+        rows.entry(proc.pid).or_default().push(proc);
+    }
+    let mut expanded = Vec::new();
+    for pid in tree_kill_order(&roots, &children) {
+        if let Some(known) = rows.remove(&pid) {
+            expanded.extend(known);
+        } else if let Some(proc) = sys.process(sysinfo::Pid::from_u32(pid)) {
+            expanded.push(ProcessInfo {
+                pid,
+                name: process_name(proc),
+                start_ticks: linux_stat(root, pid).and_then(|stat| stat.start_ticks),
+                parent: proc.parent().map(|p| p.as_u32()),
+                zombie: proc.status() == sysinfo::ProcessStatus::Zombie,
+                ..Default::default()
+            });
+        }
+    }
+    expanded
+}
+
+fn session_target(args: &Args) -> Option<u32> {
+    if args.current_session {
+        current_session()
+    } else {
+        args.session
+    }
+}
+
+fn smart_pid_filter(args: &Args) -> Option<&str> {
+    args.filter
+        .as_deref()
+        .filter(|f| args.smart_filter && !f.is_empty() && f.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn pid_matches(pid: u32, digits: &str) -> bool {
+    pid.to_string().contains(digits)
+}
+
+fn apply_filters(processes: &mut Vec<ProcessInfo>, args: &Args, root: &Path) {
+    if !args.pids.is_empty() {
+        processes.retain(|p| args.pids.contains(&p.pid));
+    }
+    if let Some(user) = &args.user {
+        processes.retain(|p| p.owner.as_deref() == Some(user.as_str()));
+    }
+    if args.min_prio.is_some() || args.max_prio.is_some() {
+        processes.retain(|p| nice_in_range(p.nice, args.min_prio, args.max_prio));
+    }
+    if args.only_with_tty {
+        processes.retain(|p| p.tty.is_some());
+    }
+    if args.no_tty {
+        processes.retain(|p| p.tty.is_none());
+    }
+    if let Some(Ok(query)) = args.query.as_deref().map(parse_query) {
+        processes.retain(|p| query_matches(&query, p));
+    }
+    if args.public_only {
+        processes.retain(|p| p.bind_addr.is_some_and(is_public_addr));
+    }
+    if !args.listening.is_empty() {
+        processes.retain(|p| args.listening.iter().any(|spec| spec.matches(p)));
+    }
+    if let Some(sid) = session_target(args) {
+        processes.retain(|p| session_matches(p.session, sid));
+    }
+    if let Some(hash) = &args.exe_hash {
+        let mut cache: HashMap<PathBuf, Option<String>> = HashMap::new();
+        processes.retain(|p| exe_hash_matches(root, p.pid, hash, &mut cache));
+    }
+    if let Some(path) = &args.has_open {
+        let wanted = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+        processes.retain(|p| holds_open(&root.join(p.pid.to_string()).join("fd"), &wanted));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum QueryToken {
+    Word(String),
+    Text(String),
+    Op(String),
+    Open,
+    Close,
+}
+
+impl fmt::Display for QueryToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryToken::Word(w) | QueryToken::Op(w) => write!(f, "{}", w),
+            QueryToken::Text(t) => write!(f, "'{}'", t),
+            QueryToken::Open => write!(f, "("),
+            QueryToken::Close => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize_query(input: &str) -> Result<Vec<QueryToken>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' || c == ')' {
+            tokens.push(if c == '(' { QueryToken::Open } else { QueryToken::Close });
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&q| q == c)
+                .ok_or_else(|| format!("unterminated string starting at position {}", i))?;
+            tokens.push(QueryToken::Text(chars[i + 1..i + 1 + end].iter().collect()));
+            i += end + 2;
+        } else if "<>=!~".contains(c) {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let op = match two.as_str() {
+                "<=" | ">=" | "==" | "!=" => two,
+                _ if c == '!' => return Err(format!("unexpected token '!' at position {}", i)),
+                _ => c.to_string(),
+            };
+            i += op.len();
+            tokens.push(QueryToken::Op(op));
+        } else {
+            let start = i;
+            while i < chars.len()
+                && !chars[i].is_whitespace()
+                && !"()<>=!~'\"".contains(chars[i])
+            {
+                i += 1;
+            }
+            tokens.push(QueryToken::Word(chars[start..i].iter().collect()));
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QueryField {
+    Cpu,
+    Mem,
+    Name,
+    Pid,
+    Port,
+    User,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QueryOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Number(QueryField, QueryOp, f64),
+    Text(QueryField, QueryOp, String),
+}
+
+struct QueryParser {
+    tokens: Vec<QueryToken>,
+    pos: usize,
+}
+
+impl QueryParser {
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(QueryToken::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn next(&mut self) -> Result<QueryToken, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| "unexpected end of expression".to_string())?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn or(&mut self) -> Result<Query, String> {
+        let mut left = self.and()?;
+        while self.peek_keyword("or") {
+            self.pos += 1;
+            left = Query::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut left = self.not()?;
+        while self.peek_keyword("and") {
+            self.pos += 1;
+            left = Query::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Query, String> {
+        if self.peek_keyword("not") {
+            self.pos += 1;
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Query, String> {
+        let field = match self.next()? {
+            QueryToken::Open => {
+                let inner = self.or()?;
+                return match self.next()? {
+                    QueryToken::Close => Ok(inner),
+                    other => Err(format!("expected ')' but found '{}'", other)),
+                };
+            }
+            QueryToken::Word(w) => match w.to_lowercase().as_str() {
+                "cpu" => QueryField::Cpu,
+                "mem" | "memory" => QueryField::Mem,
+                "name" => QueryField::Name,
+                "pid" => QueryField::Pid,
+                "port" => QueryField::Port,
+                "user" | "owner" => QueryField::User,
+                _ => return Err(format!("unknown field '{}'", w)),
+            },
+            other => return Err(format!("expected a field but found '{}'", other)),
+        };
+        let op_token = self.next()?;
+        let op = match &op_token {
+            QueryToken::Op(op) => match op.as_str() {
+                "=" | "==" => QueryOp::Eq,
+                "!=" => QueryOp::Ne,
+                "<" => QueryOp::Lt,
+                "<=" => QueryOp::Le,
+                ">" => QueryOp::Gt,
+                ">=" => QueryOp::Ge,
+                _ => QueryOp::Contains,
+            },
+            other => return Err(format!("expected an operator but found '{}'", other)),
+        };
+        let value = self.next()?;
+        let raw = match &value {
+            QueryToken::Word(w) | QueryToken::Text(w) => w.clone(),
+            other => return Err(format!("expected a value but found '{}'", other)),
+        };
+        match field {
+            QueryField::Name | QueryField::User => match op {
+                QueryOp::Eq | QueryOp::Ne | QueryOp::Contains => Ok(Query::Text(field, op, raw.to_lowercase())),
+                _ => Err(format!("unexpected token '{}', name and user only support =, != and ~", op_token)),
+            },
+            _ if op == QueryOp::Contains => Err(format!("unexpected token '{}', it only applies to name and user", op_token)),
+            _ => raw
+                .parse()
+                .map(|n| Query::Number(field, op, n))
+                .map_err(|_| format!("unexpected token '{}', expected a number", value)),
+        }
+    }
+}
+
+fn parse_query(input: &str) -> Result<Query, String> {
+    let mut parser = QueryParser {
+        tokens: tokenize_query(input)?,
+        pos: 0,
+    };
+    let query = parser.or()?;
+    match parser.tokens.get(parser.pos) {
+        Some(token) => Err(format!("unexpected token '{}'", token)),
+        None => Ok(query),
+    }
+}
+
+fn compare<T: PartialOrd>(left: T, op: QueryOp, right: T) -> bool {
+    match op {
+        QueryOp::Eq => left == right,
+        QueryOp::Ne => left != right,
+        QueryOp::Lt => left < right,
+        QueryOp::Le => left <= right,
+        QueryOp::Gt => left > right,
+        QueryOp::Ge => left >= right,
+        QueryOp::Contains => false,
+    }
+}
+
+fn query_matches(query: &Query, proc: &ProcessInfo) -> bool {
+    match query {
+        Query::And(a, b) => query_matches(a, proc) && query_matches(b, proc),
+        Query::Or(a, b) => query_matches(a, proc) || query_matches(b, proc),
+        Query::Not(inner) => !query_matches(inner, proc),
+        Query::Number(field, op, value) => {
+            let actual = match field {
+                QueryField::Cpu => Some(proc.cpu as f64),
+                QueryField::Mem => Some(proc.memory as f64 / MB as f64),
+                QueryField::Pid => Some(proc.pid as f64),
+                QueryField::Port => proc.port.map(f64::from),
+                QueryField::Name | QueryField::User => None,
+            };
+            actual.is_some_and(|actual| compare(actual, *op, *value))
+        }
+        Query::Text(field, op, value) => {
+            let actual = match field {
+                QueryField::User => proc.owner.as_deref(),
+                _ => Some(proc.name.as_str()),
+            };
+            actual.is_some_and(|actual| {
+                let actual = actual.to_lowercase();
+                match op {
+                    QueryOp::Contains => actual.contains(value.as_str()),
+                    _ => compare(actual.as_str(), *op, value.as_str()),
+                }
+            })
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> Option<String> {
+    let mut reader = BufReader::new(std::fs::File::open(path).ok()?);
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut reader, &mut hasher).ok()?;
+    Some(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn hash_matches(actual: Option<&str>, expected: &str) -> bool {
+    actual.is_some_and(|h| h.eq_ignore_ascii_case(expected))
+}
+
+fn exe_hash_matches(
+    root: &Path,
+    pid: u32,
+    expected: &str,
+    cache: &mut HashMap<PathBuf, Option<String>>,
+) -> bool {
+    let exe = root.join(pid.to_string()).join("exe");
+    // Key on the link target so processes sharing a binary are hashed once; the
+    // content is read through /proc so deleted or replaced binaries still hash.
+    let target = match std::fs::read_link(&exe) {
+        Ok(target) => target,
+        Err(_) => return false,
+    };
+    let hash = cache.entry(target).or_insert_with(|| hash_file(&exe));
+    hash_matches(hash.as_deref(), expected)
+}
+
+fn open_path_matches(target: &Path, wanted: &Path) -> bool {
+    target.starts_with(wanted)
+}
+
+// fd directories of other users' processes are unreadable without privileges;
+// those processes just don't match.
+fn holds_open(fd_dir: &Path, wanted: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(fd_dir) else {
+        return false;
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| std::fs::read_link(entry.path()).ok())
+        .any(|target| open_path_matches(&target, wanted))
+}
+
+fn is_socket_link(target: &Path) -> bool {
+    target.to_str().is_some_and(|t| t.starts_with("socket:["))
+}
+
+fn socket_total<P: AsRef<Path>>(targets: impl IntoIterator<Item = P>) -> usize {
+    targets.into_iter().filter(|t| is_socket_link(t.as_ref())).count()
+}
+
+// Every open socket, listening or connected, shows up as a `socket:[inode]` fd link.
+// Unreadable fd directories (other users' processes) stay None and render as "-".
+fn count_sockets(processes: &mut [ProcessInfo], root: &Path) {
+    if !cfg!(target_os = "linux") {
+        return;
+    }
+    for proc in processes.iter_mut() {
+        let fd_dir = root.join(proc.pid.to_string()).join("fd");
+        let Ok(entries) = std::fs::read_dir(fd_dir) else {
+            continue;
+        };
+        let targets = entries
+            .flatten()
+            .filter_map(|entry| std::fs::read_link(entry.path()).ok());
+        proc.socket_count = Some(socket_total(targets));
+    }
+}
+
+fn parse_status_field<'a>(status: &'a str, key: &str) -> Option<&'a str> {
+    status.lines().find_map(|line| {
+        let (k, v) = line.split_once(':')?;
+        (k == key).then(|| v.trim())
+    })
+}
+
+fn get_processes_from_proc(
+    root: &Path,
+    filter: Option<&str>,
+    sort_by: SortBy,
+    mem_kind: MemKind,
+) -> std::io::Result<Vec<ProcessInfo>> {
+    let name_width = calculate_name_width(false);
+    let users = Users::new_with_refreshed_list();
+    let mut processes = Vec::new();
+    let mut parents: HashMap<u32, u32> = HashMap::new();
+
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+        if entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()).is_none() {
+            continue;
+        }
+        // Read through the entry rather than linux_stat so any directory works.
+        let stat = match std::fs::read_to_string(entry.path().join("stat")) {
+            Ok(line) => match parse_proc_stat(&line) {
+                Some(stat) => stat,
+                None => continue,
+            },
+            Err(_) => continue,
+        };
+        if stat.ppid != 0 {
+            parents.insert(stat.pid, stat.ppid);
+        }
+
+        if let Some(f) = filter {
+            if !stat.comm.to_lowercase().contains(&f.to_lowercase()) {
+                continue;
+            }
+        }
+
+        let status = std::fs::read_to_string(entry.path().join("status")).unwrap_or_default();
+        let memory = parse_status_field(&status, mem_kind.status_field())
+            .and_then(|v| v.split_whitespace().next()?.parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+            .unwrap_or(0);
+        let owner = parse_status_field(&status, "Uid")
+            .and_then(|v| v.split_whitespace().next())
+            .map(|uid| owner_for_uid(uid, &users));
+
+        let cmd = std::fs::read(entry.path().join("cmdline"))
+            .map(|raw| parse_cmdline(&raw))
+            .unwrap_or_default();
+
+        processes.push(ProcessInfo {
+            pid: stat.pid,
+            name: resolve_name(&stat.comm, None, cmd.first().map(String::as_str)),
+            memory,
+            name_width,
+            owner,
+            parent: Some(stat.ppid).filter(|ppid| *ppid != 0),
+            zombie: stat.state == 'Z',
+            cmd,
+            ..Default::default()
+        });
+    }
+
+    let counts = child_counts(&parents);
+    for proc in processes.iter_mut() {
+        proc.child_count = counts.get(&proc.pid).copied().unwrap_or(0);
+    }
+
+    sort_processes(&mut processes, sort_by);
+    Ok(processes)
+}
+
+fn read_proc_path(settings: &Settings, filter: Option<&str>, sort_by: SortBy) -> Vec<ProcessInfo> {
+    let root = settings.proc_root;
+    get_processes_from_proc(root, filter, sort_by, settings.mem_kind).unwrap_or_else(|e| {
+        fail(settings, &format!("Error: failed to read {}: {}", root.display(), e), "proc_read")
+    })
+}
+
+// One-shot sample for the summary modes, honouring --proc-path.
+fn sample_processes(args: &Args, settings: &Settings) -> Vec<ProcessInfo> {
+    if args.proc_path.is_some() {
+        read_proc_path(settings, args.filter.as_deref(), args.sort)
+    } else {
+        ProcessSampler::new(true, settings.mem_kind).processes(args.filter.as_deref(), args.sort)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PortBinding {
+    port: u16,
+    protocol: String,
+    addr: IpAddr,
+    ipv4: bool,
+    ipv6: bool,
+}
+
+type PortMap = HashMap<u32, Vec<PortBinding>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PortRange {
+    start: u16,
+    end: u16,
+}
+
+impl PortRange {
+    fn contains(&self, port: u16) -> bool {
+        (self.start..=self.end).contains(&port)
+    }
+}
+
+fn parse_port_range(raw: &str) -> Result<PortRange, String> {
+    let (start, end) = raw
+        .split_once('-')
+        .ok_or_else(|| format!("expected START-END, got {}", raw))?;
+    let parse = |s: &str| {
+        s.trim()
+            .parse::<u16>()
+            .map_err(|_| format!("invalid port {}", s.trim()))
+    };
+    let range = PortRange {
+        start: parse(start)?,
+        end: parse(end)?,
+    };
+    if range.start > range.end {
+        return Err(format!("range start {} is above its end {}", range.start, range.end));
+    }
+    Ok(range)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ListenSpec {
+    protocol: &'static str,
+    port: u16,
+}
+
+impl ListenSpec {
+    fn matches(&self, proc: &ProcessInfo) -> bool {
+        proc.port == Some(self.port) && proc.protocol.as_deref() == Some(self.protocol)
+    }
+}
+
+fn parse_listen_spec(raw: &str) -> Result<ListenSpec, String> {
+    let (proto, port) = raw
+        .split_once(':')
+        .ok_or_else(|| format!("expected PROTO:PORT, got {}", raw))?;
+    let protocol = match proto.to_lowercase().as_str() {
+        "tcp" => "TCP",
+        "udp" => "UDP",
+        _ => return Err(format!("unknown protocol {} (expected tcp or udp)", proto)),
+    };
+    let port = port.parse().map_err(|_| format!("invalid port {}", port))?;
+    Ok(ListenSpec { protocol, port })
+}
+
+fn port_filter(args: &Args) -> Option<PortRange> {
+    args.port
+        .map(|port| PortRange { start: port, end: port })
+        .or(args.port_range)
+}
+
+// PID 0 is the kernel scheduler, not a process; kill(0, sig) would signal
+// rip's own process group instead.
+fn is_pseudo_pid(pid: u32) -> bool {
+    pid == 0
+}
+
+fn kill_targets(selected: Vec<ProcessInfo>) -> Vec<ProcessInfo> {
+    selected.into_iter().filter(|p| !is_pseudo_pid(p.pid)).collect()
+}
+
+// Ports mode lists one row per port, so a process holding several of them
+// would otherwise be signalled once per row.
+fn dedupe_pids(selected: Vec<ProcessInfo>) -> Vec<ProcessInfo> {
+    let mut seen = HashSet::new();
+    selected.into_iter().filter(|p| seen.insert(p.pid)).collect()
+}
+
+fn held_ports(selected: &[ProcessInfo]) -> HashMap<u32, usize> {
+    let mut held: HashMap<u32, usize> = HashMap::new();
+    for proc in selected.iter().filter(|p| p.port.is_some()) {
+        *held.entry(proc.pid).or_default() += 1;
+    }
+    held
+}
+
+fn is_public_addr(addr: IpAddr) -> bool {
+    !addr.is_loopback()
+}
+
+fn addr_label(addr: Option<IpAddr>) -> String {
+    match addr {
+        Some(addr) => truncate(&addr.to_string(), 15),
+        None => "-".to_string(),
+    }
+}
+
+fn is_udp(protocol: Option<&str>) -> bool {
+    protocol.is_some_and(|p| p.eq_ignore_ascii_case("udp"))
+}
+
+fn stack_label(binding: &PortBinding) -> &'static str {
+    match (binding.ipv4, binding.ipv6) {
+        (true, true) => "v4/v6",
+        (false, true) => "v6",
+        (true, false) => "v4",
+        (false, false) => "-",
+    }
+}
+
+fn add_binding(map: &mut PortMap, pid: u32, binding: PortBinding) {
+    let entry = map.entry(pid).or_default();
+    match entry
+        .iter_mut()
+        .find(|b| b.port == binding.port && b.protocol == binding.protocol)
+    {
+        // Keep the most exposed address when a port is bound more than once.
+        Some(existing) => {
+            if !is_public_addr(existing.addr) && is_public_addr(binding.addr) {
+                existing.addr = binding.addr;
+            }
+            existing.ipv4 |= binding.ipv4;
+            existing.ipv6 |= binding.ipv6;
+        }
+        None => entry.push(binding),
+    }
+}
+
+fn get_port_mappings() -> PortMap {
+    let mut map = PortMap::new();
+
+    if let Ok(listeners) = listeners::get_all() {
+        for listener in listeners {
+            let binding = PortBinding {
+                port: listener.socket.port(),
+                protocol: format!("{:?}", listener.protocol).to_uppercase(),
+                addr: listener.socket.ip(),
+                ipv4: listener.socket.is_ipv4(),
+                ipv6: listener.socket.is_ipv6(),
+            };
+            add_binding(&mut map, listener.process.pid, binding);
+        }
+    }
+
+    map
+}
+
+fn port_taken(map: &PortMap, port: u16) -> bool {
+    map.values().flatten().any(|binding| binding.port == port)
+}
+
+fn watch_port_done(map: &PortMap, port: u16, until_free: bool) -> bool {
+    port_taken(map, port) != until_free
+}
+
+fn wait_for_port(port: u16, until_free: bool, interval: Duration) {
+    while !watch_port_done(&get_port_mappings(), port, until_free) {
+        thread::sleep(interval);
+    }
+    println!(
+        "Port {} is {}",
+        port,
+        if until_free { Colorize::green("free") } else { Colorize::green("taken") }
+    );
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct GroupSummary {
+    key: String,
+    count: usize,
+    cpu: f32,
+    memory: u64,
+}
+
+fn group_by_user(processes: &[ProcessInfo], sort_by: SortBy) -> Vec<GroupSummary> {
+    let mut groups: HashMap<String, (usize, f32, u64)> = HashMap::new();
+
+    for proc in processes {
+        let key = proc.owner.clone().unwrap_or_else(|| "?".to_string());
+        let entry = groups.entry(key).or_insert((0, 0.0, 0));
+        entry.0 += 1;
+        entry.1 += proc.cpu;
+        entry.2 += proc.memory;
+    }
+
+    let mut summary: Vec<GroupSummary> = groups
+        .into_iter()
+        .map(|(key, (count, cpu, memory))| GroupSummary {
+            key,
+            count,
+            cpu,
+            memory,
+        })
+        .collect();
+
+    match sort_by {
+        SortBy::Cpu => summary.sort_by(|a, b| cmp_cpu(b.cpu, a.cpu)),
+        SortBy::Mem => summary.sort_by_key(|g| std::cmp::Reverse(g.memory)),
+        SortBy::Name => summary.sort_by_key(|g| g.key.to_lowercase()),
+        SortBy::Pid
+        | SortBy::Port
+        | SortBy::Children
+        | SortBy::Args
+        | SortBy::LastActive
+        | SortBy::CpuTime
+        | SortBy::Sockets
+        | SortBy::FdPct => summary.sort_by_key(|g| std::cmp::Reverse(g.count)),
+    }
+    summary
+}
+
+fn print_group_summary(summary: &[GroupSummary], label: &str, mem_kind: MemKind) {
+    let header = format!(
+        "{:<16} {:>6} {:>8} {:>10}",
+        label.to_uppercase(),
+        "PROCS",
+        "CPU %",
+        mem_kind.label()
+    );
+    println!("{}", Colorize::dimmed(header.as_str()));
+
+    for group in summary {
+        println!(
+            "{:<16} {:>6} {:>7.1}% {:>10}",
+            truncate(&group.key, 16),
+            group.count,
+            group.cpu,
+            format_memory(group.memory)
+        );
+    }
+}
+
+const CPU_BUCKETS: [f32; 2] = [10.0, 50.0];
+const CPU_BUCKET_LABELS: [&str; 3] = ["0-10%", "10-50%", "50%+"];
+const MEM_BUCKETS: [u64; 3] = [100, 500, 1024];
+const MEM_BUCKET_LABELS: [&str; 4] = ["0-100 MB", "100-500 MB", "500 MB-1 GB", "1 GB+"];
+const HISTOGRAM_BAR_WIDTH: usize = 40;
+
+fn bucket_counts<T: PartialOrd>(values: impl Iterator<Item = T>, bounds: &[T]) -> Vec<usize> {
+    let mut counts = vec![0; bounds.len() + 1];
+    for value in values {
+        let bucket = bounds.iter().position(|b| value < *b).unwrap_or(bounds.len());
+        counts[bucket] += 1;
+    }
+    counts
+}
+
+fn print_histogram(title: &str, labels: &[&str], counts: &[usize]) {
+    println!("{}", Colorize::bold(title));
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    for (label, count) in labels.iter().zip(counts) {
+        let bar = "█".repeat((count * HISTOGRAM_BAR_WIDTH).div_ceil(max));
+        println!("  {:<12} {:>6} {}", label, count, Colorize::cyan(bar.as_str()));
+    }
+}
+
+fn build_port_rows(
+    port_map: &PortMap,
+    names: &HashMap<u32, String>,
+    filter: Option<&str>,
+    port_filter: Option<PortRange>,
+) -> Vec<ProcessInfo> {
+    let name_width = calculate_name_width(true);
+    let mut rows: Vec<ProcessInfo> = port_map
+        .iter()
+        .flat_map(|(pid, ports)| {
+            let name = names.get(pid).cloned().unwrap_or_else(|| "?".to_string());
+            ports.iter().map(move |binding| ProcessInfo {
+                pid: *pid,
+                name: name.clone(),
+                name_width,
+                port: Some(binding.port),
+                protocol: Some(binding.protocol.clone()),
+                bind_addr: Some(binding.addr),
+                stack: stack_label(binding),
+                ..Default::default()
+            })
+        })
+        .filter(|row| {
+            port_filter.is_none_or(|range| row.port.is_some_and(|port| range.contains(port)))
+        })
+        .filter(|row| filter.is_none_or(|f| row.name.to_lowercase().contains(&f.to_lowercase())))
+        .collect();
+
+    rows.sort_by(|a, b| {
+        a.port
+            .cmp(&b.port)
+            .then_with(|| a.protocol.cmp(&b.protocol))
+            .then_with(|| a.pid.cmp(&b.pid))
+    });
+    rows
+}
+
+// listeners can report a PID that has already exited (or that sysinfo could not
+// read); without this those ports would silently vanish from --ports output.
+fn orphan_port_rows(
+    port_map: &PortMap,
+    known: &HashSet<u32>,
+    filter: Option<&str>,
+    port_filter: Option<PortRange>,
+) -> Vec<ProcessInfo> {
+    let orphans: PortMap = port_map
+        .iter()
+        .filter(|(pid, _)| !known.contains(pid))
+        .map(|(pid, ports)| (*pid, ports.clone()))
+        .collect();
+    let names: HashMap<u32, String> = orphans
+        .keys()
+        .map(|pid| (*pid, "<unknown>".to_string()))
+        .collect();
+    build_port_rows(&orphans, &names, filter, port_filter)
+}
+
+fn join_entries(entries: &[String], null: bool) -> String {
+    let separator = if null { '\0' } else { '\n' };
+    let mut out = String::new();
+    for entry in entries {
+        out.push_str(entry);
+        out.push(separator);
+    }
+    out
+}
+
+fn write_entries(path: Option<&Path>, entries: &[String], null: bool) -> std::io::Result<()> {
+    let out = join_entries(entries, null);
+    match path {
+        Some(path) if path != Path::new("-") => std::fs::write(path, out),
+        _ => {
+            let mut stdout = stdout().lock();
+            stdout.write_all(out.as_bytes())?;
+            stdout.flush()
+        }
+    }
+}
+
+fn parse_signal(signal_str: &str) -> Result<Signal, String> {
+    let signal_str = signal_str.to_uppercase();
+    let signal_str = signal_str.strip_prefix("SIG").unwrap_or(&signal_str);
+
+    #[cfg(unix)]
+    {
+        match signal_str {
+            "KILL" | "9" => Ok(Signal::SIGKILL),
+            "TERM" | "15" => Ok(Signal::SIGTERM),
+            "INT" | "2" => Ok(Signal::SIGINT),
+            "HUP" | "1" => Ok(Signal::SIGHUP),
+            "QUIT" | "3" => Ok(Signal::SIGQUIT),
+            "USR1" | "10" => Ok(Signal::SIGUSR1),
+            "USR2" | "12" => Ok(Signal::SIGUSR2),
+            "STOP" | "19" => Ok(Signal::SIGSTOP),
+            "CONT" | "18" => Ok(Signal::SIGCONT),
+            _ => Err(format!("Unknown signal: {}", signal_str)),
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        match signal_str {
+            "KILL" | "9" => Ok(Signal::Kill),
+            "TERM" | "15" => Ok(Signal::Term),
+            _ => Err(format!("Only KILL and TERM are supported on Windows: {}", signal_str)),
+        }
+    }
+}
+
+fn selector_order(mut processes: Vec<ProcessInfo>, selector_sort: Option<SortBy>) -> Vec<ProcessInfo> {
+    if let Some(sort_by) = selector_sort {
+        sort_processes(&mut processes, sort_by);
+    }
+    processes
+}
+
+fn pick_by_age(processes: &[ProcessInfo], oldest: bool) -> Option<ProcessInfo> {
+    // Ties go to the lower PID, which is usually the earlier spawn.
+    let by_run_time = |a: &&ProcessInfo, b: &&ProcessInfo| {
+        a.run_time.cmp(&b.run_time).then_with(|| b.pid.cmp(&a.pid))
+    };
+    if oldest {
+        processes.iter().max_by(by_run_time).cloned()
+    } else {
+        processes.iter().min_by(by_run_time).cloned()
+    }
+}
+
+fn format_run_time(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+        s if s < 86400 => format!("{}h {}m", s / 3600, s % 3600 / 60),
+        s => format!("{}d {}h", s / 86400, s % 86400 / 3600),
+    }
+}
+
+// Criteria that still match a process after it is restarted, unlike a PID.
+fn has_match_filter(args: &Args) -> bool {
+    args.filter.is_some()
+        || args.query.is_some()
+        || port_filter(args).is_some()
+        || !args.listening.is_empty()
+}
+
+fn has_narrowing_filter(args: &Args) -> bool {
+    args.filter.is_some()
+        || args.query.is_some()
+        || port_filter(args).is_some()
+        || !args.listening.is_empty()
+        || args.session.is_some()
+        || args.current_session
+        || args.exe_hash.is_some()
+        || args.has_open.is_some()
+        || !args.pids.is_empty()
+}
+
+fn safe_mode_error(args: &Args, safe: bool) -> Option<String> {
+    let destructive = !(args.names
+        || args.write_pids.is_some()
+        || args.output.is_some()
+        || args.histogram
+        || args.group_by.is_some()
+        || !args.diff.is_empty()
+        || args.watch_port.is_some()
+        || args.on_match.is_some());
+    (safe && destructive && !has_narrowing_filter(args)).then(|| {
+        "Error: safe mode requires a filter (-f, --query, --port, --session, --exe-hash, --has-open or --pid) before killing anything.".to_string()
+    })
+}
+
+// Naming every target by PID is already as specific as a confirmation, so asking
+// again only adds friction. Anything looser (a partial match, a name filter) still asks.
+fn explicit_pid_selection(requested: &[u32], matched: &[ProcessInfo]) -> bool {
+    !requested.is_empty() && matched.iter().all(|p| requested.contains(&p.pid))
+}
+
+fn missing_pids(requested: &[u32], matched: &[ProcessInfo]) -> Vec<u32> {
+    let found: HashSet<u32> = matched.iter().map(|p| p.pid).collect();
+    let mut seen = HashSet::new();
+    requested
+        .iter()
+        .copied()
+        .filter(|pid| !found.contains(pid) && seen.insert(*pid))
+        .collect()
+}
+
+fn confirm_kill(settings: &Settings, selected: &[ProcessInfo]) -> bool {
+    ask_confirm(
+        settings,
+        &format!(
+            "Kill {} process{}?",
+            selected.len(),
+            if selected.len() == 1 { "" } else { "es" }
+        ),
+        false,
+    )
+}
+
+fn confirm_exec(settings: &Settings, template: &str, selected: &[ProcessInfo]) -> bool {
+    ask_confirm(
+        settings,
+        &format!(
+            "Run `{}` on {} process{}?",
+            template,
+            selected.len(),
+            if selected.len() == 1 { "" } else { "es" }
+        ),
+        false,
+    )
+}
+
+fn confirm_single(settings: &Settings, proc: &ProcessInfo) -> bool {
+    ask_confirm(
+        settings,
+        &format!(
+            "Kill {} (PID: {}, running {})?",
+            proc.name,
+            proc.pid,
+            format_run_time(proc.run_time)
+        ),
+        false,
+    )
+}
+
+fn selector_header(columns: Columns, name_width: usize, ports_mode: bool) -> String {
+    let mut headers = Vec::new();
+    if ports_mode {
+        headers.push(format!("{:<9}", port_header(columns)));
+    }
+    if columns.stack {
+        headers.push(format!("{:<5}", "STACK"));
+    }
+    if columns.bind_addr {
+        headers.push(format!("{:<15}", "ADDRESS"));
+    }
+    headers.extend([
+        format!("{:<7}", "PID"),
+        format!("{:<width$}", "NAME", width = name_width),
+    ]);
+    if !columns.hide_usage {
+        headers.extend([format!("{:>7}", "CPU %"), format!("{:>9}", columns.settings.mem_kind.label())]);
+    }
+    if columns.children {
+        headers.push(format!("{:>width$}", "CHILDREN", width = CHILDREN_WIDTH));
+    }
+    if columns.foreground {
+        headers.push(format!("{:<2}", "FG"));
+    }
+    if columns.tty {
+        headers.push(format!("{:<7}", "TTY"));
+    }
+    if columns.cpu_time {
+        headers.push(format!("{:>9}", "CPU TIME"));
+    }
+    if columns.sockets {
+        headers.push(format!("{:>width$}", "SOCKETS", width = SOCKETS_WIDTH));
+    }
+    if columns.fd_usage {
+        headers.push(format!("{:>6}", "FD %"));
+    }
+    if columns.prio {
+        headers.push(format!("{:>4}", "PRIO"));
+    }
+    if columns.owner {
+        headers.push(format!("{:<width$}", "OWNER", width = OWNER_WIDTH));
+    }
+    let header: Vec<String> = headers
+        .iter()
+        .map(|h| paint(h.as_str(), columns.settings.theme.dim).to_string())
+        .collect();
+    format!("    {}\n", header.join(columns.settings.gap))
+}
+
+const MB: u64 = 1024 * 1024;
+
+// Memory is kept in bytes; tiny processes read as KB instead of "0 MB".
+fn format_memory(bytes: u64) -> String {
+    if bytes >= 1024 * MB {
+        format!("{:.1} GB", bytes as f64 / (1024 * MB) as f64)
+    } else if bytes >= MB {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    } else {
+        format!("{} KB", bytes / 1024)
+    }
+}
+
+fn selection_summary(processes: &[ProcessInfo]) -> String {
+    let memory: u64 = processes.iter().map(|p| p.memory).sum();
+    let cpu: f32 = processes.iter().map(|p| p.cpu).sum();
+    format!(
+        "{} {} matched, total {}, {:.1}% CPU",
+        processes.len(),
+        if processes.len() == 1 { "process" } else { "processes" },
+        format_memory(memory),
+        cpu
+    )
+}
+
+const SELECTOR_CAP: usize = 1000;
+
+fn cap_selector(mut processes: Vec<ProcessInfo>, cap: usize) -> (Vec<ProcessInfo>, Option<usize>) {
+    if processes.len() <= cap {
+        return (processes, None);
+    }
+    let total = processes.len();
+    processes.truncate(cap);
+    (processes, Some(total))
+}
+
+const SELECTOR_HELP: &str = "↑↓ navigate • Space select • Enter confirm • Type to filter";
+
+// --minimal drops the column header and the help line, leaving only the rows.
+fn selector_chrome(
+    first: &ProcessInfo,
+    ports_mode: bool,
+    minimal: bool,
+) -> (String, Option<&'static str>) {
+    if minimal {
+        return (String::new(), None);
+    }
+    (selector_header(first.columns, first.name_width, ports_mode), Some(SELECTOR_HELP))
+}
+
+fn run_selector(processes: Vec<ProcessInfo>, ports_mode: bool, minimal: bool) -> Vec<ProcessInfo> {
+    if processes.is_empty() {
+        return vec![];
+    }
+
+    // inquire re-renders every option on each keystroke, which gets sluggish
+    // with thousands of rows, so only hand it the top of the list.
+    let (processes, total) = cap_selector(processes, SELECTOR_CAP);
+    if let Some(total) = total {
+        eprintln!(
+            "{} showing the first {} of {} processes; narrow with -f, --query or --limit",
+            Colorize::yellow("Note:"),
+            SELECTOR_CAP,
+            total
+        );
+    }
+
+    let (header, help) = selector_chrome(&processes[0], ports_mode, minimal);
+    let mut select = MultiSelect::new(&header, processes).with_page_size(15);
+    if let Some(help) = help {
+        select = select.with_help_message(help);
+    }
+
+    select.prompt().unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StepDecision {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+fn parse_step_answer(answer: &str) -> StepDecision {
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => StepDecision::Yes,
+        "a" | "all" => StepDecision::All,
+        "q" | "quit" => StepDecision::Quit,
+        _ => StepDecision::No,
+    }
+}
+
+fn step_through<F>(selected: Vec<ProcessInfo>, mut ask: F) -> Vec<ProcessInfo>
+where
+    F: FnMut(&ProcessInfo) -> StepDecision,
+{
+    let mut to_kill = Vec::new();
+    let mut remaining = selected.into_iter();
+
+    while let Some(proc) = remaining.next() {
+        match ask(&proc) {
+            StepDecision::Yes => to_kill.push(proc),
+            StepDecision::No => {}
+            StepDecision::All => {
+                to_kill.push(proc);
+                to_kill.extend(remaining);
+                break;
+            }
+            StepDecision::Quit => return vec![],
+        }
+    }
+
+    to_kill
+}
+
+fn prompt_step(settings: &Settings, proc: &ProcessInfo) -> StepDecision {
+    let prompt = format!("Kill {} ({})? [y/N/a/q]", proc.name, proc.pid);
+    let answer = match settings.confirm_style {
+        ConfirmStyle::Fancy => Text::new(&prompt).prompt().ok(),
+        ConfirmStyle::Plain => read_plain_answer(&prompt),
+    };
+    answer.map_or(StepDecision::Quit, |a| parse_step_answer(&a))
+}
+
+// Returns how many processes the final kill attempted and how many it signalled,
+// both zero when the view is left without killing.
+fn run_live_mode(
+    args: &Args,
+    config: &Config,
+    signal: Signal,
+    settings: &Settings,
+) -> std::io::Result<(usize, usize)> {
+    let filter = args.filter.as_deref();
+    let mut sort_by = args.sort;
+    let port_filter = port_filter(args);
+    let ports_mode = args.ports
+        || port_filter.is_some()
+        || !args.listening.is_empty()
+        || args.by_port
+        || args.public_only;
+    let include_self = args.include_self;
+    let shell = if args.force { HashSet::new() } else { shell_pids(settings.proc_root) };
+    let adaptive = args.adaptive;
+    let columns = Columns::from_args(args, *settings);
+    let theme = settings.theme;
+    let mut csv_log = match &args.log_csv {
+        Some(path) => Some(open_csv_log(path)?),
+        None => None,
+    };
+
+    let alt_screen = !args.no_alt_screen;
+    enable_raw_mode()?;
+    if alt_screen {
+        stdout().execute(EnterAlternateScreen)?;
+    }
+    let mut terminal = if alt_screen {
+        Terminal::new(CrosstermBackend::new(stdout()))?
+    } else {
+        let (_, height) = crossterm::terminal::size()?;
+        Terminal::with_options(
+            CrosstermBackend::new(stdout()),
+            TerminalOptions {
+                viewport: Viewport::Inline(inline_height(height)),
+            },
+        )?
+    };
+
+    let mut table_state = TableState::default();
+    table_state.select(Some(0));
+    let mut selected_pids: HashSet<u32> = HashSet::new();
+    let mut last_refresh = Instant::now();
+    let mut stamp = process_table_stamp();
+    let mode = refresh_mode(args.interval, stamp.is_some(), running_over_ssh());
+    let mut refresh_interval = match mode {
+        RefreshMode::Every(interval) => interval,
+        RefreshMode::OnChange => Duration::ZERO,
+    };
+    let poll_interval = poll_interval(running_over_ssh());
+    let fetch = |sampler: &mut ProcessSampler, filter: Option<&str>, sort_by: SortBy| {
+        if args.by_port {
+            sampler.port_rows(filter, port_filter)
+        } else if ports_mode {
+            sampler.processes_with_ports(filter, port_filter, sort_by, args.show_orphan_ports)
+        } else {
+            sampler.processes(filter, sort_by)
+        }
+    };
+    let sample = |sampler: &mut ProcessSampler, sort_by: SortBy| {
+        let mut processes = fetch(sampler, filter, sort_by);
+        if let Some(digits) = smart_pid_filter(args).filter(|_| processes.is_empty()) {
+            processes = fetch(sampler, None, sort_by);
+            processes.retain(|p| pid_matches(p.pid, digits));
+        }
+        if args.sum_thread_cpu {
+            processes = merge_thread_cpu(processes);
+            sort_processes(&mut processes, sort_by);
+        }
+        enrich_processes(&mut processes, settings.proc_root);
+        if args.show_sockets || sort_by == SortBy::Sockets {
+            count_sockets(&mut processes, settings.proc_root);
+        }
+        if args.show_fd_usage || sort_by == SortBy::FdPct {
+            fill_fd_usage(&mut processes, settings.proc_root);
+        }
+        if !args.pin.is_empty() {
+            mark_pinned(&mut processes, &args.pin);
+        }
+        if sorts_after_enrich(sort_by) || !args.pin.is_empty() {
+            sort_processes(&mut processes, sort_by);
+        }
+        apply_filters(&mut processes, args, settings.proc_root);
+        if args.dedupe_by == Some(DedupeBy::Name) {
+            processes = dedupe_by_name(processes);
+        }
+        if args.shuffle {
+            shuffle_processes(&mut processes, shuffle_seed());
+        }
+        if let Some(limit) = args.limit {
+            processes.truncate(limit);
+        }
+        if args.tree {
+            processes = tree_order(processes, args.show_spawn_order);
+        }
+        if !include_self {
+            mark_self(&mut processes);
+        }
+        mark_shell(&mut processes, &shell);
+        mark_danger(&mut processes, &config.danger_ports);
+        let mut columns = columns;
+        if ports_mode && !args.always_show_proto {
+            columns.single_protocol = single_protocol(&processes);
+        }
+        apply_columns(&mut processes, columns, ports_mode);
+        processes
+    };
+    let mut baselines: HashMap<u32, u64> = HashMap::new();
+    let mut alerted: HashSet<u32> = HashSet::new();
+    let mut track_growth = |processes: &mut Vec<ProcessInfo>| {
+        let Some(threshold) = args.mem_alert else {
+            return;
+        };
+        memory_growth(&mut baselines, processes);
+        alerted.retain(|pid| processes.iter().any(|p| p.pid == *pid));
+        let mut newly_alerted = false;
+        for proc in processes.iter() {
+            if exceeds_mem_alert(proc.mem_growth, threshold) && alerted.insert(proc.pid) {
+                newly_alerted = true;
+            }
+        }
+        if newly_alerted && args.beep {
+            print!("\x07");
+            let _ = stdout().flush();
+        }
+    };
+    let mut cpu_window = args.cpu_window.map(|ms| CpuWindow::new(Duration::from_millis(ms)));
+    let mut sampler = ProcessSampler::new(true, settings.mem_kind);
+    let mut processes = sample(&mut sampler, sort_by);
+    track_growth(&mut processes);
+    if let Some(window) = cpu_window.as_mut() {
+        window.update(&mut processes, Instant::now());
+    }
+    let mut last_active: HashMap<u32, u64> = HashMap::new();
+    record_activity(&mut processes, &mut last_active, 0);
+    let mut status: Option<String> = None;
+    if let Some(log) = csv_log.as_mut() {
+        if let Err(e) = append_csv(log, &processes) {
+            status = Some(format!("CSV log failed: {}", e));
+            csv_log = None;
+        }
+    }
+    let mut show_confirm = false;
+    let mut single_target: Option<u32> = None;
+    let mut show_args: Option<ProcessInfo> = None;
+    let mut show_legend = false;
+    let mut args_scroll: u16 = 0;
+    let mut watch: Option<Watch> = None;
+    let mut collapsed: HashSet<u32> = HashSet::new();
+    let mut last_global_cpu = sampler.system().global_cpu_usage();
+    let mut system_cpu: VecDeque<u64> = VecDeque::new();
+    push_capped(&mut system_cpu, last_global_cpu.round() as u64, SYSTEM_CPU_HISTORY);
+    let mut refreshes: u64 = 0;
+
+    loop {
+        let due = refresh_due(mode, last_refresh.elapsed(), refresh_interval, || {
+            process_table_stamp() != stamp
+        });
+        if due && !show_confirm && show_args.is_none() {
+            stamp = process_table_stamp();
+            processes = sample(&mut sampler, sort_by);
+            track_growth(&mut processes);
+            if let Some(window) = cpu_window.as_mut() {
+                window.update(&mut processes, Instant::now());
+            }
+            record_activity(&mut processes, &mut last_active, refreshes + 1);
+            let windowed = cpu_window.is_some() && sort_by == SortBy::Cpu;
+            if windowed || sort_by == SortBy::LastActive {
+                sort_processes(&mut processes, sort_by);
+                if args.tree {
+                    processes = tree_order(processes, args.show_spawn_order);
+                }
+            }
+            let global_cpu = sampler.system().global_cpu_usage();
+            push_capped(&mut system_cpu, global_cpu.round() as u64, SYSTEM_CPU_HISTORY);
+            if adaptive {
+                refresh_interval =
+                    adjust_refresh_interval(refresh_interval, (global_cpu - last_global_cpu).abs());
+                last_global_cpu = global_cpu;
+            }
+            last_refresh = Instant::now();
+            refreshes += 1;
+            if refresh_limit_reached(refreshes, args.refresh_count) {
+                selected_pids.clear();
+                break;
+            }
+            let exited = reconcile_selection(&mut selected_pids, &processes);
+            status = match exited.as_slice() {
+                [] => None,
+                [pid] => Some(format!("process {} exited, deselected", pid)),
+                pids => Some(format!("{} processes exited, deselected", pids.len())),
+            };
+            if let Some(log) = csv_log.as_mut() {
+                if let Err(e) = append_csv(log, &processes) {
+                    status = Some(format!("CSV log failed: {}", e));
+                    csv_log = None;
+                }
+            }
+            collapsed.retain(|pid| processes.iter().any(|p| p.pid == *pid));
+            if let Some(watch) = watch.as_mut() {
+                watch.update(processes.iter().find(|p| p.pid == watch.pid));
+            }
+        }
+
+        let visible = visible_rows(&processes, &collapsed);
+        if let Some(selected) = table_state.selected() {
+            if selected >= visible.len() && !visible.is_empty() {
+                table_state.select(Some(visible.len() - 1));
+            }
+        }
+
+        let kill_pids = kill_set(&selected_pids, single_target);
+        let selected_danger_ports = if show_confirm {
+            let selected: Vec<ProcessInfo> = processes
+                .iter()
+                .filter(|p| kill_pids.contains(&p.pid))
+                .cloned()
+                .collect();
+            danger_ports_in(&selected)
+        } else {
+            Vec::new()
+        };
+
+        terminal.draw(|frame| {
+            let area = frame.area();
+            let [table_area, footer_area] =
+                Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(area);
+            let columns = processes.first().map_or(columns, |p| p.columns);
+            let name_width = live_name_width(area.width, columns, ports_mode);
+            let rows: Vec<Row> = visible
+                .iter()
+                .map(|&i| {
+                    let p = &processes[i];
+                    let is_selected = selected_pids.contains(&p.pid);
+                    let leaking = args
+                        .mem_alert
+                        .is_some_and(|threshold| exceeds_mem_alert(p.mem_growth, threshold));
+                    let marker = if is_selected { "●" } else { " " };
+                    let cpu_style = if p.cpu > 50.0 {
+                        Style::default().fg(theme.hot).bold()
+                    } else if p.cpu > 10.0 {
+                        Style::default().fg(theme.warm)
+                    } else {
+                        Style::default().fg(theme.dim)
+                    };
+
+                    let mut cells = vec![
+                        Cell::from(marker).style(if is_selected {
+                            Style::default().fg(theme.good).bold()
+                        } else {
+                            Style::default()
+                        }),
+                    ];
+
+                    if ports_mode {
+                        let port_str = p
+                            .port
+                            .map(|port| format!("{:<5}", port))
+                            .unwrap_or_default();
+                        let proto_str = match columns.single_protocol {
+                            Some(_) => "",
+                            None => p.protocol.as_deref().unwrap_or(""),
+                        };
+                        cells.push(
+                            Cell::from(format!("{} {:>3}", port_str, proto_str)).style(
+                                if p.danger {
+                                    Style::default().fg(theme.port(p)).bold()
+                                } else {
+                                    Style::default().fg(theme.port(p))
+                                },
+                            ),
+                        );
+                    }
+                    if columns.stack {
+                        cells.push(Cell::from(p.stack).style(Style::default().fg(theme.dim)));
+                    }
+                    if columns.bind_addr {
+                        cells.push(
+                            Cell::from(addr_label(p.bind_addr))
+                                .style(Style::default().fg(theme.dim)),
+                        );
+                    }
+
+                    cells.extend([
+                        Cell::from(format!("{:<7}", p.pid))
+                            .style(Style::default().fg(theme.dim)),
+                        Cell::from(truncate_with(
+                            &format!(
+                                "{}{}{}",
+                                p.tree_prefix,
+                                if collapsed.contains(&p.pid) { "▸ " } else { "" },
+                                display_label(p)
+                            ),
+                            name_width,
+                            columns.truncate,
+                        ))
+                        .style(if p.is_self || p.is_shell {
+                            Style::default().fg(theme.own).italic()
+                        } else {
+                            Style::default().fg(theme.text)
+                        }),
+                    ]);
+                    if !columns.hide_usage {
+                        cells.extend([
+                            Cell::from(format!("{:>6.1}%", p.cpu)).style(cpu_style),
+                            Cell::from(format!("{:>9}", format_memory(p.memory)))
+                                .style(Style::default().fg(theme.accent)),
+                        ]);
+                    }
+                    if columns.mem_growth {
+                        cells.push(Cell::from(format!("{:>9}", format_growth(p.mem_growth))).style(
+                            if leaking {
+                                Style::default().fg(theme.hot).bold()
+                            } else {
+                                Style::default().fg(theme.dim)
+                            },
+                        ));
+                    }
+                    if columns.children {
+                        cells.push(
+                            Cell::from(format!("{:>width$}", p.child_count, width = CHILDREN_WIDTH))
+                                .style(Style::default().fg(theme.dim)),
+                        );
+                    }
+                    if columns.foreground {
+                        cells.push(Cell::from(foreground_label(p.foreground)).style(
+                            if p.foreground == Some(true) {
+                                Style::default().fg(theme.accent)
+                            } else {
+                                Style::default().fg(theme.dim)
+                            },
+                        ));
+                    }
+                    if columns.tty {
+                        cells.push(
+                            Cell::from(p.tty.clone().unwrap_or_else(|| "-".to_string()))
+                                .style(Style::default().fg(theme.dim)),
+                        );
+                    }
+                    if columns.cpu_time {
+                        cells.push(
+                            Cell::from(format!("{:>9}", optional_cpu_time(p.cpu_time)))
+                                .style(Style::default().fg(theme.dim)),
+                        );
+                    }
+                    if columns.sockets {
+                        let sockets = format!("{:>width$}", optional_count(p.socket_count), width = SOCKETS_WIDTH);
+                        cells.push(Cell::from(sockets).style(Style::default().fg(theme.dim)));
+                    }
+                    if columns.fd_usage {
+                        cells.push(
+                            Cell::from(format!("{:>7}", fd_usage_label(p.fd_usage))).style(
+                                if p.fd_usage.is_some_and(|pct| pct >= FD_USAGE_WARN) {
+                                    Style::default().fg(theme.hot)
+                                } else {
+                                    Style::default().fg(theme.dim)
+                                },
+                            ),
+                        );
+                    }
+                    if columns.prio {
+                        cells.push(
+                            Cell::from(format!("{:>4}", optional_nice(p.nice)))
+                                .style(Style::default().fg(theme.dim)),
+                        );
+                    }
+                    if columns.owner {
+                        cells.push(Cell::from(owner_label(p)).style(Style::default().fg(theme.dim)));
+                    }
+
+                    let row = Row::new(cells);
+                    if leaking {
+                        row.style(Style::default().fg(theme.hot))
+                    } else {
+                        row
+                    }
+                })
+                .collect();
+
+            let header_style = Style::default().fg(theme.dim);
+            let header_cell = |text: String, column: SortBy| {
+                Cell::from(text).style(if column == sort_by && !args.by_port {
+                    Style::default().fg(theme.text)
+                } else {
+                    header_style
+                })
+            };
+            let label = |text: &str, column: SortBy| {
+                if args.by_port {
+                    text.to_string()
+                } else {
+                    sort_header(text, column, sort_by)
+                }
+            };
+            let mut header_cells = vec![Cell::from(" ")];
+            let mut widths = vec![Constraint::Length(2)];
+            if ports_mode {
+                header_cells.push(header_cell(
+                    format!("{:<9}", label(&port_header(columns), SortBy::Port)),
+                    SortBy::Port,
+                ));
+                widths.push(Constraint::Length(9));
+            }
+            if columns.stack {
+                header_cells.push(Cell::from("STACK").style(header_style));
+                widths.push(Constraint::Length(5));
+            }
+            if columns.bind_addr {
+                header_cells.push(Cell::from("ADDRESS").style(header_style));
+                widths.push(Constraint::Length(15));
+            }
+            header_cells.extend([
+                header_cell(format!("{:<7}", label("PID", SortBy::Pid)), SortBy::Pid),
+                header_cell(label("NAME", SortBy::Name), SortBy::Name),
+            ]);
+            widths.extend([Constraint::Length(7), Constraint::Min(20)]);
+            if !columns.hide_usage {
+                header_cells.extend([
+                    header_cell(format!("{:>7}", label("CPU %", SortBy::Cpu)), SortBy::Cpu),
+                    header_cell(format!("{:>9}", label(settings.mem_kind.label(), SortBy::Mem)), SortBy::Mem),
+                ]);
+                widths.extend([Constraint::Length(7), Constraint::Length(9)]);
+            }
+            if columns.mem_growth {
+                header_cells.push(Cell::from(format!("{:>9}", "GROWTH")).style(header_style));
+                widths.push(Constraint::Length(9));
+            }
+            if columns.children {
+                header_cells.push(header_cell(
+                    format!("{:>width$}", label("CHILDREN", SortBy::Children), width = CHILDREN_WIDTH),
+                    SortBy::Children,
+                ));
+                widths.push(Constraint::Length(CHILDREN_WIDTH as u16));
+            }
+            if columns.foreground {
+                header_cells.push(Cell::from("FG").style(header_style));
+                widths.push(Constraint::Length(2));
+            }
+            if columns.tty {
+                header_cells.push(Cell::from("TTY").style(header_style));
+                widths.push(Constraint::Length(7));
+            }
+            if columns.cpu_time {
+                header_cells.push(header_cell(
+                    format!("{:>9}", label("CPU TIME", SortBy::CpuTime)),
+                    SortBy::CpuTime,
+                ));
+                widths.push(Constraint::Length(11));
+            }
+            if columns.sockets {
+                header_cells.push(header_cell(
+                    format!("{:>width$}", label("SOCKETS", SortBy::Sockets), width = SOCKETS_WIDTH),
+                    SortBy::Sockets,
+                ));
+                widths.push(Constraint::Length(SOCKETS_WIDTH as u16));
+            }
+            if columns.fd_usage {
+                header_cells.push(header_cell(
+                    format!("{:>7}", label("FD %", SortBy::FdPct)),
+                    SortBy::FdPct,
+                ));
+                widths.push(Constraint::Length(7));
+            }
+            if columns.prio {
+                header_cells.push(Cell::from("PRIO").style(header_style));
+                widths.push(Constraint::Length(4));
+            }
+            if columns.owner {
+                header_cells.push(Cell::from("OWNER").style(header_style));
+                widths.push(Constraint::Length(OWNER_WIDTH as u16));
+            }
+            let header = Row::new(header_cells).style(Style::default().bold());
+
+            let title = live_title(
+                selected_pids.len(),
+                hidden_selected(&selected_pids, &processes, &visible),
+                signal,
+                !config.signal_rules.is_empty(),
+                status.as_deref(),
+            );
+
+            let table = Table::new(rows, widths)
+                .header(header)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .title_bottom(" ↑↓ navigate • Space select • Enter kill • K kill highlighted • a args • w watch • e export • s sort • S save sort • L legend • q quit "),
+                )
+                .row_highlight_style(Style::default().bg(theme.highlight).fg(theme.text))
+                .highlight_symbol("▶ ");
+
+            frame.render_stateful_widget(table, table_area, &mut table_state);
+
+            let [cpu_label_area, cpu_spark_area] =
+                Layout::horizontal([Constraint::Length(14), Constraint::Min(1)]).areas(footer_area);
+            frame.render_widget(
+                Paragraph::new(format!(
+                    " system {:>3}% ",
+                    system_cpu.back().copied().unwrap_or(0)
+                ))
+                .style(Style::default().fg(theme.dim)),
+                cpu_label_area,
+            );
+            let skip = system_cpu.len().saturating_sub(cpu_spark_area.width as usize);
+            let history: Vec<u64> = system_cpu.iter().skip(skip).copied().collect();
+            frame.render_widget(
+                Sparkline::default()
+                    .data(&history)
+                    .max(100)
+                    .style(Style::default().fg(theme.good)),
+                cpu_spark_area,
+            );
+
+            if let Some(watch) = &watch {
+                frame.render_widget(Clear, area);
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" watching {} ({}) ", watch.name, watch.pid))
+                    .title_bottom(" Esc back ")
+                    .border_style(Style::default().fg(theme.accent));
+                let inner = block.inner(area);
+                frame.render_widget(block, area);
+
+                let rows = Layout::vertical([
+                    Constraint::Length(2),
+                    Constraint::Min(3),
+                    Constraint::Min(3),
+                ])
+                .split(inner);
+                let summary = if watch.exited {
+                    Line::from("process exited").style(Style::default().fg(theme.hot).bold())
+                } else {
+                    Line::from(format!(
+                        "CPU {:>5}%   MEM {:>9}   FDs {:>5}   threads {:>4}   cores {}",
+                        watch.cpu.back().copied().unwrap_or(0),
+                        format_memory(watch.memory.back().copied().unwrap_or(0)),
+                        optional_count(watch.fds),
+                        optional_count(watch.threads),
+                        watch.cores.as_deref().unwrap_or("n/a"),
+                    ))
+                };
+                frame.render_widget(Paragraph::new(summary), rows[0]);
+
+                let skip = watch.cpu.len().saturating_sub(inner.width as usize);
+                let cpu: Vec<u64> = watch.cpu.iter().skip(skip).copied().collect();
+                let memory: Vec<u64> = watch.memory.iter().skip(skip).copied().collect();
+                frame.render_widget(
+                    Sparkline::default()
+                        .block(Block::default().title("CPU %"))
+                        .data(&cpu)
+                        .style(Style::default().fg(theme.warm)),
+                    rows[1],
+                );
+                frame.render_widget(
+                    Sparkline::default()
+                        .block(Block::default().title("Memory"))
+                        .data(&memory)
+                        .style(Style::default().fg(theme.accent)),
+                    rows[2],
+                );
+            }
+
+            if let Some(proc) = &show_args {
+                let popup_area = centered_rect(80, 60, area);
+                frame.render_widget(Clear, popup_area);
+
+                let inner_width = popup_area.width.saturating_sub(2) as usize;
+                let inner_height = popup_area.height.saturating_sub(2) as usize;
+                let lines = if proc.cmd.is_empty() {
+                    vec!["(no command line, likely a kernel thread)".to_string()]
+                } else {
+                    wrap_words(&proc.cmd.join(" "), inner_width)
+                };
+                let max_scroll = lines.len().saturating_sub(inner_height) as u16;
+                args_scroll = args_scroll.min(max_scroll);
+
+                let text: Vec<Line> = lines.into_iter().map(Line::from).collect();
+                let popup = Paragraph::new(text)
+                    .wrap(Wrap { trim: false })
+                    .scroll((args_scroll, 0))
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title(format!(" {} ({}) ", proc.name, proc.pid))
+                            .title_bottom(" ↑↓ scroll • Esc close ")
+                            .border_style(Style::default().fg(theme.accent)),
+                    );
+                frame.render_widget(popup, popup_area);
+            }
+
+            if show_legend {
+                let popup_area = centered_rect(60, 60, area);
+                frame.render_widget(Clear, popup_area);
+                let text: Vec<Line> = legend(theme)
+                    .iter()
+                    .map(|(sample, style, meaning)| {
+                        Line::from(vec![
+                            Span::styled(format!("{:<10}", sample), *style),
+                            Span::raw(*meaning),
+                        ])
+                    })
+                    .collect();
+                let popup = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(" Legend ")
+                        .title_bottom(" L/Esc close ")
+                        .border_style(Style::default().fg(theme.accent)),
+                );
+                frame.render_widget(popup, popup_area);
+            }
+
+            if show_confirm {
+                let popup_area = centered_rect(50, 20, area);
+                frame.render_widget(Clear, popup_area);
+
+                let count = kill_pids.len();
+                let text = if selected_danger_ports.is_empty() {
+                    format!(
+                        "Kill {} process{}?\n\n[Enter] Confirm  [Esc] Cancel",
+                        count,
+                        if count == 1 { "" } else { "es" }
+                    )
+                } else {
+                    let ports: Vec<String> =
+                        selected_danger_ports.iter().map(|p| p.to_string()).collect();
+                    format!(
+                        "Kill {} process{}?\nIncludes danger port(s) {}!\n\n[y] Confirm  [Esc] Cancel",
+                        count,
+                        if count == 1 { "" } else { "es" },
+                        ports.join(", ")
+                    )
+                };
+                let popup = Paragraph::new(text)
+                    .alignment(Alignment::Center)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title(" Confirm ")
+                            .border_style(Style::default().fg(theme.warm)),
+                    );
+                frame.render_widget(popup, popup_area);
+            }
+        })?;
+
+        if event::poll(poll_interval)? {
+            let event = event::read()?;
+            if let Event::Resize(..) = event {
+                // The next draw reflows to the new width; clearing first keeps a
+                // shrink from leaving stale cells past the new edge.
+                terminal.clear()?;
+            }
+            if let Event::Key(key) = event {
+                if key.kind == KeyEventKind::Press {
+                    if watch.is_some() {
+                        if closes_overlay(key.code, 'w') {
+                            watch = None;
+                        }
+                    } else if show_legend {
+                        if closes_overlay(key.code, 'L') {
+                            show_legend = false;
+                        }
+                    } else if show_args.is_some() {
+                        match key.code {
+                            KeyCode::Esc | KeyCode::Char('a') | KeyCode::Char('q') => {
+                                show_args = None;
+                                args_scroll = 0;
+                            }
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                args_scroll = args_scroll.saturating_sub(1);
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                args_scroll = args_scroll.saturating_add(1);
+                            }
+                            _ => {}
+                        }
+                    } else if show_confirm {
+                        match key.code {
+                            KeyCode::Enter if selected_danger_ports.is_empty() => {
+                                selected_pids = kill_pids;
+                                break;
+                            }
+                            KeyCode::Char('y') if !selected_danger_ports.is_empty() => {
+                                selected_pids = kill_pids;
+                                break;
+                            }
+                            KeyCode::Esc => {
+                                show_confirm = false;
+                                single_target = None;
+                            }
+                            _ => {}
+                        }
+                    } else {
+                        match key.code {
+                            KeyCode::Char('q') | KeyCode::Esc => {
+                                selected_pids.clear();
+                                break;
+                            }
+                            KeyCode::Char('a') => {
+                                show_args = table_state
+                                    .selected()
+                                    .and_then(|i| visible.get(i))
+                                    .map(|&i| processes[i].clone());
+                            }
+                            KeyCode::Char('L') => show_legend = true,
+                            KeyCode::Char('w') => {
+                                watch = table_state
+                                    .selected()
+                                    .and_then(|i| visible.get(i))
+                                    .map(|&i| Watch::new(&processes[i], settings.proc_root));
+                            }
+                            KeyCode::Left | KeyCode::Char('h') if args.tree => {
+                                if let Some(&i) = table_state.selected().and_then(|i| visible.get(i)) {
+                                    collapsed.insert(processes[i].pid);
+                                }
+                            }
+                            KeyCode::Right | KeyCode::Char('l') if args.tree => {
+                                if let Some(&i) = table_state.selected().and_then(|i| visible.get(i)) {
+                                    collapsed.remove(&processes[i].pid);
+                                }
+                            }
+                            KeyCode::Char('e') => {
+                                let timestamp = SystemTime::now()
+                                    .duration_since(UNIX_EPOCH)
+                                    .map(|d| d.as_secs())
+                                    .unwrap_or(0);
+                                status = Some(match write_snapshot(Path::new("."), &processes, timestamp) {
+                                    Ok(path) => format!("saved snapshot to {}", path.display()),
+                                    Err(e) => format!("could not save snapshot: {}", e),
+                                });
+                            }
+                            KeyCode::Char('s') if !args.by_port => {
+                                sort_by = next_sort(sort_by, ports_mode);
+                                sort_processes(&mut processes, sort_by);
+                                if args.tree {
+                                    processes = tree_order(
+                                        std::mem::take(&mut processes),
+                                        args.show_spawn_order,
+                                    );
+                                }
+                                status = Some(format!("sort: {}", sort_name(sort_by)));
+                            }
+                            KeyCode::Char('S') => {
+                                status = Some(match save_default_sort(sort_by) {
+                                    Ok(path) => format!(
+                                        "saved sort {} to {}",
+                                        sort_name(sort_by),
+                                        path.display()
+                                    ),
+                                    Err(e) => format!("could not save config: {}", e),
+                                });
+                            }
+                            KeyCode::Up | KeyCode::Char('k') => {
+                                if let Some(selected) = table_state.selected() {
+                                    if selected > 0 {
+                                        table_state.select(Some(selected - 1));
+                                    }
+                                }
+                            }
+                            KeyCode::Down | KeyCode::Char('j') => {
+                                if let Some(selected) = table_state.selected() {
+                                    if selected < visible.len().saturating_sub(1) {
+                                        table_state.select(Some(selected + 1));
+                                    }
+                                }
+                            }
+                            KeyCode::Char(' ') => {
+                                if let Some(selected) = table_state.selected() {
+                                    if let Some(proc) = visible.get(selected).map(|&i| &processes[i]) {
+                                        if selected_pids.contains(&proc.pid) {
+                                            selected_pids.remove(&proc.pid);
+                                        } else {
+                                            selected_pids.insert(proc.pid);
+                                        }
+                                    }
+                                }
+                            }
+                            KeyCode::Enter if !selected_pids.is_empty() => {
+                                show_confirm = true;
+                            }
+                            KeyCode::Char('K') => {
+                                single_target = table_state
+                                    .selected()
+                                    .and_then(|i| visible.get(i))
+                                    .map(|&i| processes[i].pid);
+                                show_confirm = single_target.is_some();
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    disable_raw_mode()?;
+    if alt_screen {
+        stdout().execute(LeaveAlternateScreen)?;
+    } else {
+        println!();
+    }
+
+    if !selected_pids.is_empty() {
+        let to_kill: Vec<ProcessInfo> = processes
+            .into_iter()
+            .filter(|p| selected_pids.contains(&p.pid))
+            .collect();
+        let mut targets = dedupe_pids(handle_zombies(settings, guard_self(to_kill), args.reap_zombies));
+        attach_pidfds(&mut targets, settings.proc_root);
+        let attempted = targets.len();
+        let signalled = kill_processes(settings, targets, signal, &config.signal_rules);
+        if !args.quiet {
+            report_survivors(&signalled);
+        }
+        return Ok((attempted, signalled.len()));
+    }
+
+    Ok((0, 0))
+}
+
+fn tsv_field(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+const SNAPSHOT_COLUMNS: [&str; 9] = [
+    "pid",
+    "name",
+    "cpu",
+    "memory_mb",
+    "port",
+    "protocol",
+    "owner",
+    "start_time",
+    "command",
+];
+
+fn snapshot_tsv(processes: &[ProcessInfo], header: Option<&str>) -> String {
+    let mut out = String::new();
+    if let Some(marker) = header {
+        out.push_str(marker);
+        out.push_str(&SNAPSHOT_COLUMNS.join("\t"));
+        out.push('\n');
+    }
+    for p in processes {
+        let row = [
+            p.pid.to_string(),
+            tsv_field(&p.name),
+            format!("{:.1}", p.cpu),
+            (p.memory / MB).to_string(),
+            p.port.map(|port| port.to_string()).unwrap_or_default(),
+            p.protocol.clone().unwrap_or_default(),
+            tsv_field(p.owner.as_deref().unwrap_or_default()),
+            p.start_time.to_string(),
+            tsv_field(&p.cmd.join(" ")),
+        ];
+        out.push_str(&row.join("\t"));
+        out.push('\n');
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SnapshotRow {
+    pid: u32,
+    start_time: Option<u64>,
+    name: String,
+    cpu: f32,
+    memory: u64,
+}
+
+// Columns are located through the header row when there is one (with or without
+// the `#` marker), so snapshots written before a column was added still load.
+fn parse_snapshot(text: &str) -> Result<Vec<SnapshotRow>, String> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty()).peekable();
+    let header: Vec<String> = match lines.peek() {
+        Some(first) if first.trim_start_matches('#').starts_with("pid\t") => lines
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches('#')
+            .split('\t')
+            .map(str::to_string)
+            .collect(),
+        _ => SNAPSHOT_COLUMNS.iter().map(|c| c.to_string()).collect(),
+    };
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let pid_at = column("pid").ok_or("snapshot has no pid column")?;
+
+    let mut rows = Vec::new();
+    for (lineno, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let field = |index: Option<usize>| index.and_then(|i| fields.get(i)).copied().unwrap_or("");
+        let pid = field(Some(pid_at))
+            .parse()
+            .map_err(|_| format!("row {}: invalid pid {:?}", lineno + 1, field(Some(pid_at))))?;
+        rows.push(SnapshotRow {
+            pid,
+            start_time: field(column("start_time")).parse().ok(),
+            name: field(column("name")).to_string(),
+            cpu: field(column("cpu")).parse().unwrap_or(0.0),
+            memory: field(column("memory_mb")).parse().unwrap_or(0),
+        });
+    }
+    Ok(rows)
+}
+
+const DIFF_CPU_CHANGE: f32 = 10.0;
+const DIFF_MEM_CHANGE_MB: u64 = 50;
+
+#[derive(Debug, Default, PartialEq)]
+struct SnapshotDiff {
+    added: Vec<SnapshotRow>,
+    removed: Vec<SnapshotRow>,
+    changed: Vec<(SnapshotRow, SnapshotRow)>,
+}
+
+// A PID is the same process in both snapshots only if its start time matches
+// too; otherwise it was reused and counts as one removal plus one addition.
+// Ports mode repeats a process once per port, so rows are deduplicated first.
+fn diff_snapshots(old: &[SnapshotRow], new: &[SnapshotRow]) -> SnapshotDiff {
+    let key = |r: &SnapshotRow| (r.pid, r.start_time);
+    let mut old_rows: HashMap<(u32, Option<u64>), &SnapshotRow> = HashMap::new();
+    for row in old {
+        old_rows.entry(key(row)).or_insert(row);
+    }
+    let mut diff = SnapshotDiff::default();
+    let mut seen = HashSet::new();
+    for row in new {
+        if !seen.insert(key(row)) {
+            continue;
+        }
+        match old_rows.get(&key(row)) {
+            None => diff.added.push(row.clone()),
+            Some(before) => {
+                let cpu_moved = (row.cpu - before.cpu).abs() >= DIFF_CPU_CHANGE;
+                let mem_moved = row.memory.abs_diff(before.memory) >= DIFF_MEM_CHANGE_MB;
+                if cpu_moved || mem_moved {
+                    diff.changed.push(((*before).clone(), row.clone()));
+                }
+            }
+        }
+    }
+    let mut gone = HashSet::new();
+    for row in old {
+        if !seen.contains(&key(row)) && gone.insert(key(row)) {
+            diff.removed.push(row.clone());
+        }
+    }
+    diff
+}
+
+fn print_snapshot_diff(diff: &SnapshotDiff) {
+    println!("{} ({})", Colorize::bold("Added"), diff.added.len());
+    for row in &diff.added {
+        println!("  {} {:<7} {}", Colorize::green("+"), row.pid, row.name);
+    }
+    println!("{} ({})", Colorize::bold("Removed"), diff.removed.len());
+    for row in &diff.removed {
+        println!("  {} {:<7} {}", Colorize::red("-"), row.pid, row.name);
+    }
+    println!("{} ({})", Colorize::bold("Changed"), diff.changed.len());
+    for (before, after) in &diff.changed {
+        println!(
+            "  {} {:<7} {}  CPU {:.1}% -> {:.1}%  MEM {} MB -> {} MB",
+            Colorize::yellow("~"),
+            after.pid,
+            after.name,
+            before.cpu,
+            after.cpu,
+            before.memory,
+            after.memory
+        );
+    }
+}
+
+fn load_snapshot(path: &Path) -> Result<Vec<SnapshotRow>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Error: failed to read {}: {}", path.display(), e))?;
+    parse_snapshot(&text).map_err(|e| format!("Error: {}: {}", path.display(), e))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+const CSV_HEADER: &str = "timestamp,pid,name,cpu,memory_mb\n";
+
+fn csv_rows(processes: &[ProcessInfo], timestamp: u64) -> String {
+    let mut out = String::new();
+    for p in processes {
+        out.push_str(&format!(
+            "{},{},{},{:.1},{}\n",
+            timestamp,
+            p.pid,
+            csv_field(&p.name),
+            p.cpu,
+            p.memory / MB
+        ));
+    }
+    out
+}
+
+fn open_csv_log(path: &Path) -> std::io::Result<std::io::BufWriter<std::fs::File>> {
+    let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    let empty = file.metadata()?.len() == 0;
+    let mut log = std::io::BufWriter::new(file);
+    if empty {
+        log.write_all(CSV_HEADER.as_bytes())?;
+    }
+    Ok(log)
+}
+
+fn append_csv(log: &mut impl Write, processes: &[ProcessInfo]) -> std::io::Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    log.write_all(csv_rows(processes, timestamp).as_bytes())?;
+    log.flush()
+}
+
+fn write_snapshot(dir: &Path, processes: &[ProcessInfo], timestamp: u64) -> std::io::Result<PathBuf> {
+    let path = dir.join(format!("rip-snapshot-{}.tsv", timestamp));
+    std::fs::write(&path, snapshot_tsv(processes, Some("")))?;
+    Ok(path)
+}
+
+// Selected rows inside a collapsed subtree still get killed on Enter.
+fn hidden_selected(selected: &HashSet<u32>, processes: &[ProcessInfo], visible: &[usize]) -> usize {
+    let shown: HashSet<u32> = visible.iter().map(|&i| processes[i].pid).collect();
+    selected.iter().filter(|pid| !shown.contains(pid)).count()
+}
+
+fn live_title(
+    selected: usize,
+    hidden: usize,
+    signal: Signal,
+    has_rules: bool,
+    status: Option<&str>,
+) -> String {
+    let mut title = " rip ".to_string();
+    if selected > 0 {
+        title.push_str(&format!("- {} selected ", selected));
+    }
+    if hidden > 0 {
+        title.push_str(&format!("({} hidden) ", hidden));
+    }
+    let name = signal_label(signal);
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    title.push_str(&format!("- signal: {}{} ", name, if has_rules { " (+rules)" } else { "" }));
+    if let Some(msg) = status {
+        title.push_str(&format!("- {} ", msg));
+    }
+    title
+}
+
+const WATCH_HISTORY: usize = 120;
+const SYSTEM_CPU_HISTORY: usize = 60;
+
+fn push_capped(buffer: &mut VecDeque<u64>, value: u64, cap: usize) {
+    while buffer.len() >= cap {
+        buffer.pop_front();
+    }
+    buffer.push_back(value);
+}
+
+struct Watch {
+    pid: u32,
+    name: String,
+    cpu: VecDeque<u64>,
+    memory: VecDeque<u64>,
+    fds: Option<usize>,
+    threads: Option<usize>,
+    cores: Option<String>,
+    exited: bool,
+    root: &'static Path,
+}
+
+impl Watch {
+    fn new(proc: &ProcessInfo, root: &'static Path) -> Self {
+        let mut watch = Watch {
+            pid: proc.pid,
+            name: proc.name.clone(),
+            cpu: VecDeque::new(),
+            memory: VecDeque::new(),
+            fds: None,
+            threads: None,
+            cores: None,
+            exited: false,
+            root,
+        };
+        watch.update(Some(proc));
+        watch
+    }
+
+    fn update(&mut self, proc: Option<&ProcessInfo>) {
+        let Some(proc) = proc.filter(|p| p.pid == self.pid && !self.exited) else {
+            self.exited = true;
+            return;
+        };
+        push_capped(&mut self.cpu, proc.cpu.round() as u64, WATCH_HISTORY);
+        push_capped(&mut self.memory, proc.memory, WATCH_HISTORY);
+        self.fds = count_fds(self.root, proc.pid);
+        self.threads = thread_count(self.root, proc.pid);
+        self.cores = cpu_affinity(self.root, proc.pid);
+    }
+}
+
+fn count_fds(root: &Path, pid: u32) -> Option<usize> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    std::fs::read_dir(root.join(pid.to_string()).join("fd"))
+        .ok()
+        .map(|entries| entries.count())
+}
+
+// The soft limit is the one open() fails against with EMFILE; "unlimited" gives None.
+fn parse_open_files_limit(limits: &str) -> Option<u64> {
+    let line = limits.lines().find(|l| l.starts_with("Max open files"))?;
+    line["Max open files".len()..].split_whitespace().next()?.parse().ok()
+}
+
+fn fd_percent(open: usize, limit: u64) -> Option<f32> {
+    (limit > 0).then(|| open as f32 / limit as f32 * 100.0)
+}
+
+const FD_USAGE_WARN: f32 = 80.0;
+
+fn fd_usage_label(pct: Option<f32>) -> String {
+    pct.map(|p| format!("{:.1}%", p)).unwrap_or_else(|| "-".to_string())
+}
+
+fn fill_fd_usage(processes: &mut [ProcessInfo], root: &Path) {
+    if !cfg!(target_os = "linux") {
+        return;
+    }
+    for proc in processes.iter_mut() {
+        let dir = root.join(proc.pid.to_string());
+        let limit = std::fs::read_to_string(dir.join("limits"))
+            .ok()
+            .and_then(|limits| parse_open_files_limit(&limits));
+        proc.fd_usage = count_fds(root, proc.pid)
+            .zip(limit)
+            .and_then(|(open, limit)| fd_percent(open, limit));
+    }
+}
+
+fn thread_count(root: &Path, pid: u32) -> Option<usize> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let status = std::fs::read_to_string(root.join(pid.to_string()).join("status")).ok()?;
+    parse_status_field(&status, "Threads")?.trim().parse().ok()
+}
+
+// Cpus_allowed is a comma-grouped hex mask with core 0 in the lowest bit of the
+// last digit, e.g. `ffffffff,0000000f`.
+fn parse_cpu_mask(mask: &str) -> Option<Vec<usize>> {
+    let mut cores = Vec::new();
+    for (nibble, digit) in mask.trim().chars().filter(|c| *c != ',').rev().enumerate() {
+        let bits = digit.to_digit(16)?;
+        for bit in 0..4 {
+            if bits & (1 << bit) != 0 {
+                cores.push(nibble * 4 + bit);
+            }
+        }
+    }
+    Some(cores)
+}
+
+fn format_core_list(cores: &[usize]) -> String {
+    let mut ranges: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < cores.len() {
+        let start = cores[i];
+        while i + 1 < cores.len() && cores[i + 1] == cores[i] + 1 {
+            i += 1;
+        }
+        ranges.push(if cores[i] == start {
+            start.to_string()
+        } else {
+            format!("{}-{}", start, cores[i])
+        });
+        i += 1;
+    }
+    ranges.join(",")
+}
+
+fn cpu_affinity(root: &Path, pid: u32) -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let status = std::fs::read_to_string(root.join(pid.to_string()).join("status")).ok()?;
+    let cores = parse_cpu_mask(parse_status_field(&status, "Cpus_allowed")?)?;
+    Some(format_core_list(&cores))
+}
+
+fn optional_count(count: Option<usize>) -> String {
+    count.map(|c| c.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+const SSH_INTERVAL_MS: u64 = 5000;
+
+fn running_over_ssh() -> bool {
+    std::env::var_os("SSH_CONNECTION").is_some() || std::env::var_os("SSH_TTY").is_some()
+}
+
+fn default_interval_ms(over_ssh: bool) -> u64 {
+    if over_ssh {
+        SSH_INTERVAL_MS
+    } else {
+        2000
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RefreshMode {
+    Every(Duration),
+    OnChange,
+}
+
+fn refresh_mode(interval_ms: u64, events_available: bool, over_ssh: bool) -> RefreshMode {
+    match interval_ms {
+        0 if events_available => RefreshMode::OnChange,
+        0 => RefreshMode::Every(Duration::from_millis(default_interval_ms(over_ssh))),
+        ms => RefreshMode::Every(Duration::from_millis(ms)),
+    }
+}
+
+// A host that forks constantly would otherwise redraw on every poll, so
+// change-driven refreshes are spaced at least this far apart.
+const ON_CHANGE_MIN_INTERVAL: Duration = Duration::from_millis(1000);
+
+// The process table is only checked once the minimum spacing has passed, so an
+// idle view costs one small read per poll tick and nothing before that.
+fn refresh_due(
+    mode: RefreshMode,
+    elapsed: Duration,
+    interval: Duration,
+    table_changed: impl FnOnce() -> bool,
+) -> bool {
+    match mode {
+        RefreshMode::Every(_) => elapsed >= interval,
+        RefreshMode::OnChange => elapsed >= ON_CHANGE_MIN_INTERVAL && table_changed(),
+    }
+}
+
+// The last two fields of /proc/loadavg are "running/total last_pid". The task
+// total and the newest PID move whenever a process starts or exits, and reading
+// them is far cheaper than a full refresh, so they are checked on every poll.
+fn process_table_stamp() -> Option<(u64, u64)> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let loadavg = std::fs::read_to_string("/proc/loadavg").ok()?;
+    let mut fields = loadavg.split_whitespace().skip(3);
+    let total = fields.next()?.split('/').nth(1)?.parse().ok()?;
+    let last_pid = fields.next()?.parse().ok()?;
+    Some((total, last_pid))
+}
+
+fn poll_interval(over_ssh: bool) -> Duration {
+    if over_ssh {
+        Duration::from_millis(250)
+    } else {
+        Duration::from_millis(100)
+    }
+}
+
+const ADAPTIVE_MIN_INTERVAL: Duration = Duration::from_millis(500);
+const ADAPTIVE_MAX_INTERVAL: Duration = Duration::from_millis(5000);
+
+fn adjust_refresh_interval(current: Duration, volatility: f32) -> Duration {
+    let next = if volatility > 10.0 {
+        current / 2
+    } else if volatility < 2.0 {
+        current * 3 / 2
+    } else {
+        current
+    };
+    next.clamp(ADAPTIVE_MIN_INTERVAL, ADAPTIVE_MAX_INTERVAL)
+}
+
+fn clock_ticks_per_sec() -> f64 {
+    #[cfg(target_os = "linux")]
+    {
+        use nix::unistd::{sysconf, SysconfVar};
+        if let Ok(Some(ticks)) = sysconf(SysconfVar::CLK_TCK) {
+            return ticks as f64;
+        }
+    }
+    100.0
+}
+
+fn windowed_cpu(delta_ticks: u64, ticks_per_sec: f64, elapsed: Duration) -> f32 {
+    let secs = elapsed.as_secs_f64();
+    if secs <= 0.0 {
+        return 0.0;
+    }
+    (delta_ticks as f64 / ticks_per_sec / secs * 100.0) as f32
+}
+
+// CPU % over a fixed window: each process keeps a short history of (time, utime + stime)
+// samples, and usage is measured against the newest sample that is at least `window` old,
+// so the figure doesn't depend on how often the view refreshes.
+struct CpuWindow {
+    window: Duration,
+    ticks_per_sec: f64,
+    history: HashMap<u32, VecDeque<(Instant, u64)>>,
+}
+
+impl CpuWindow {
+    fn new(window: Duration) -> Self {
+        CpuWindow {
+            window,
+            ticks_per_sec: clock_ticks_per_sec(),
+            history: HashMap::new(),
+        }
+    }
+
+    fn update(&mut self, processes: &mut [ProcessInfo], now: Instant) {
+        let alive: HashSet<u32> = processes.iter().map(|p| p.pid).collect();
+        self.history.retain(|pid, _| alive.contains(pid));
+        for proc in processes.iter_mut() {
+            let Some(ticks) = proc.cpu_ticks else {
+                continue;
+            };
+            let samples = self.history.entry(proc.pid).or_default();
+            while samples.len() > 1 && now.duration_since(samples[1].0) >= self.window {
+                samples.pop_front();
+            }
+            if let Some(&(then, old_ticks)) = samples.front() {
+                proc.cpu = windowed_cpu(
+                    ticks.saturating_sub(old_ticks),
+                    self.ticks_per_sec,
+                    now.duration_since(then),
+                );
+            }
+            samples.push_back((now, ticks));
+        }
+    }
+}
+
+// Leave the prompt line visible below an inline frame, but never shrink it past
+// the header and a few rows.
+fn inline_height(terminal_height: u16) -> u16 {
+    terminal_height.saturating_sub(1).max(5)
+}
+
+fn refresh_limit_reached(refreshes: u64, limit: u64) -> bool {
+    limit > 0 && refreshes >= limit
+}
+
+fn kill_set(selected_pids: &HashSet<u32>, single_target: Option<u32>) -> HashSet<u32> {
+    match single_target {
+        Some(pid) => HashSet::from([pid]),
+        None => selected_pids.clone(),
+    }
+}
+
+fn reconcile_selection(selected_pids: &mut HashSet<u32>, processes: &[ProcessInfo]) -> Vec<u32> {
+    let alive: HashSet<u32> = processes.iter().map(|p| p.pid).collect();
+    let mut exited: Vec<u32> = selected_pids
+        .iter()
+        .filter(|pid| !alive.contains(pid))
+        .copied()
+        .collect();
+    exited.sort_unstable();
+    for pid in &exited {
+        selected_pids.remove(pid);
+    }
+    exited
+}
+
+fn memory_growth(baselines: &mut HashMap<u32, u64>, processes: &mut [ProcessInfo]) {
+    let alive: HashSet<u32> = processes.iter().map(|p| p.pid).collect();
+    baselines.retain(|pid, _| alive.contains(pid));
+    for proc in processes.iter_mut() {
+        let mb = proc.memory / MB;
+        let baseline = *baselines.entry(proc.pid).or_insert(mb);
+        proc.mem_growth = mb as i64 - baseline as i64;
+    }
+}
+
+fn exceeds_mem_alert(growth: i64, threshold_mb: u64) -> bool {
+    growth > threshold_mb as i64
+}
+
+fn format_growth(growth: i64) -> String {
+    if growth > 0 {
+        format!("+{} MB", growth)
+    } else {
+        format!("{} MB", growth)
+    }
+}
+
+// Owns one System for the whole run so live refreshes update it in place instead
+// of enumerating every process from scratch. CPU usage is a delta between two
+// refreshes, so the first sample takes two passes and later ones only wait if
+// the previous refresh was too recent to measure against. Without `cpu` a
+// single pass is enough and cpu_usage() just reads 0.
+struct ProcessSampler {
+    sys: System,
+    cpu: bool,
+    mem_kind: MemKind,
+    last_refresh: Option<Instant>,
+}
+
+impl ProcessSampler {
+    fn new(cpu: bool, mem_kind: MemKind) -> Self {
+        ProcessSampler {
+            sys: System::new(),
+            cpu,
+            mem_kind,
+            last_refresh: None,
+        }
+    }
+
+    fn system(&self) -> &System {
+        &self.sys
+    }
+
+    fn refresh(&mut self) -> &System {
+        match self.last_refresh {
+            _ if !self.cpu => {}
+            Some(last) => {
+                let since = last.elapsed();
+                if since < sysinfo::MINIMUM_CPU_UPDATE_INTERVAL {
+                    thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL - since);
+                }
+            }
+            None => {
+                self.sys.refresh_all();
+                thread::sleep(Duration::from_millis(200));
+            }
+        }
+        self.sys.refresh_all();
+        self.last_refresh = Some(Instant::now());
+        &self.sys
+    }
+
+    fn processes(&mut self, filter: Option<&str>, sort_by: SortBy) -> Vec<ProcessInfo> {
+        let mem_kind = self.mem_kind;
+        process_rows(self.refresh(), filter, sort_by, mem_kind)
+    }
+
+    fn processes_with_ports(
+        &mut self,
+        filter: Option<&str>,
+        port_filter: Option<PortRange>,
+        sort_by: SortBy,
+        show_orphans: bool,
+    ) -> Vec<ProcessInfo> {
+        let mem_kind = self.mem_kind;
+        port_process_rows(self.refresh(), filter, port_filter, sort_by, show_orphans, mem_kind)
+    }
+
+    // One row per port needs names only, so skip the CPU pass.
+    fn port_rows(
+        &mut self,
+        filter: Option<&str>,
+        port_filter: Option<PortRange>,
+    ) -> Vec<ProcessInfo> {
+        self.sys.refresh_processes(sysinfo::ProcessesToUpdate::All, true);
+        let names: HashMap<u32, String> = self
+            .sys
+            .processes()
+            .iter()
+            .map(|(pid, proc)| (pid.as_u32(), process_name(proc)))
+            .collect();
+        build_port_rows(&get_port_mappings(), &names, filter, port_filter)
+    }
+}
+
+fn process_rows(
+    sys: &System,
+    filter: Option<&str>,
+    sort_by: SortBy,
+    mem_kind: MemKind,
+) -> Vec<ProcessInfo> {
+    let name_width = calculate_name_width(false);
+    let users = Users::new_with_refreshed_list();
+    let children = child_counts(&parent_map(sys));
+
+    let mut processes: Vec<ProcessInfo> = sys
+        .processes()
+        .iter()
+        .filter_map(|(pid, proc)| {
+            if is_pseudo_pid(pid.as_u32()) {
+                return None;
+            }
+            let name = process_name(proc);
+
+            if let Some(f) = filter {
+                if !name.to_lowercase().contains(&f.to_lowercase()) {
+                    return None;
+                }
+            }
+
+            Some(ProcessInfo {
+                pid: pid.as_u32(),
+                name,
+                cpu: proc.cpu_usage(),
+                memory: process_memory(proc, mem_kind),
+                name_width,
+                owner: resolve_owner(proc, &users),
+                start_time: proc.start_time(),
+                run_time: proc.run_time(),
+                child_count: children.get(&pid.as_u32()).copied().unwrap_or(0),
+                parent: proc.parent().map(|p| p.as_u32()),
+                thread_of: proc
+                    .parent()
+                    .filter(|_| proc.thread_kind() == Some(ThreadKind::Userland))
+                    .map(|p| p.as_u32()),
+                zombie: proc.status() == sysinfo::ProcessStatus::Zombie,
+                cmd: command_line(proc),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    sort_processes(&mut processes, sort_by);
+    processes
+}
+
+fn port_process_rows(
+    sys: &System,
+    filter: Option<&str>,
+    port_filter: Option<PortRange>,
+    sort_by: SortBy,
+    show_orphans: bool,
+    mem_kind: MemKind,
+) -> Vec<ProcessInfo> {
+    let port_map = get_port_mappings();
+    let name_width = calculate_name_width(true);
+    let users = Users::new_with_refreshed_list();
+    let children = child_counts(&parent_map(sys));
+
+    let mut processes: Vec<ProcessInfo> = sys
+        .processes()
+        .iter()
+        .flat_map(|(pid, proc)| {
+            let pid_u32 = pid.as_u32();
+            if is_pseudo_pid(pid_u32) {
+                return vec![];
+            }
+            let ports = match port_map.get(&pid_u32) {
+                Some(p) => p,
+                None => return vec![],
+            };
+
+            let name = process_name(proc);
+
+            if let Some(f) = filter {
+                if !name.to_lowercase().contains(&f.to_lowercase()) {
+                    return vec![];
+                }
+            }
+
+            let cpu = proc.cpu_usage();
+            let memory = process_memory(proc, mem_kind);
+            let owner = resolve_owner(proc, &users);
+            let cmd = command_line(proc);
+
+            ports
+                .iter()
+                .filter_map(|binding| {
+                    if let Some(range) = port_filter {
+                        if !range.contains(binding.port) {
+                            return None;
+                        }
+                    }
+
+                    Some(ProcessInfo {
+                        pid: pid_u32,
+                        name: name.clone(),
+                        cpu,
+                        memory,
+                        name_width,
+                        port: Some(binding.port),
+                        protocol: Some(binding.protocol.clone()),
+                        bind_addr: Some(binding.addr),
+                        stack: stack_label(binding),
+                        owner: owner.clone(),
+                        start_time: proc.start_time(),
+                        run_time: proc.run_time(),
+                        child_count: children.get(&pid_u32).copied().unwrap_or(0),
+                        cmd: cmd.clone(),
+                        ..Default::default()
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    if show_orphans {
+        let known: HashSet<u32> = sys.processes().keys().map(|pid| pid.as_u32()).collect();
+        processes.extend(orphan_port_rows(&port_map, &known, filter, port_filter));
+    }
+
+    sort_processes(&mut processes, sort_by);
+    processes
+}
+
+// An overlay closes on Esc, q, or the same key that opened it.
+fn closes_overlay(code: KeyCode, toggle: char) -> bool {
+    matches!(code, KeyCode::Esc | KeyCode::Char('q')) || code == KeyCode::Char(toggle)
+}
+
+fn legend(theme: Theme) -> [(&'static str, Style, &'static str); 10] {
+    [
+        ("●", Style::new().fg(theme.good).add_modifier(Modifier::BOLD), "selected for killing"),
+        ("CPU", Style::new().fg(theme.hot).add_modifier(Modifier::BOLD), "above 50%"),
+        ("CPU", Style::new().fg(theme.warm), "above 10%"),
+        ("CPU", Style::new().fg(theme.dim), "10% or less"),
+        ("MEM", Style::new().fg(theme.accent), "resident (or virtual) memory"),
+        ("PORT", Style::new().fg(theme.good), "TCP listener"),
+        ("PORT", Style::new().fg(theme.udp), "UDP socket"),
+        ("PORT", Style::new().fg(theme.hot).add_modifier(Modifier::BOLD), "danger port, asks before killing"),
+        ("NAME", Style::new().fg(theme.own).add_modifier(Modifier::ITALIC), "rip itself or your shell"),
+        ("row", Style::new().fg(theme.hot), "memory grew past --mem-alert"),
+    ]
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let popup_layout = Layout::vertical([
+        Constraint::Percentage((100 - percent_y) / 2),
+        Constraint::Percentage(percent_y),
+        Constraint::Percentage((100 - percent_y) / 2),
+    ])
+    .split(r);
+
+    Layout::horizontal([
+        Constraint::Percentage((100 - percent_x) / 2),
+        Constraint::Percentage(percent_x),
+        Constraint::Percentage((100 - percent_x) / 2),
+    ])
+    .split(popup_layout[1])[1]
+}
+
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn request_stop(_: i32) {
+    STOP_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+fn install_stop_handler() {
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet};
+        let action = SigAction::new(
+            SigHandler::Handler(request_stop),
+            SaFlags::empty(),
+            SigSet::empty(),
+        );
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+        unsafe {
+            let _ = sigaction(Signal::SIGINT, &action);
+        }
+    }
+}
+
+fn stop_requested(flag: &AtomicBool) -> bool {
+    flag.load(Ordering::SeqCst)
+}
+
+#[cfg(target_os = "linux")]
+type PidFd = std::os::fd::OwnedFd;
+#[cfg(not(target_os = "linux"))]
+type PidFd = ();
+
+#[derive(Debug, PartialEq)]
+enum PidFdError {
+    Gone,
+    Unsupported,
+}
+
+// pidfd_open needs Linux 5.3; on older kernels (ENOSYS) or other platforms the
+// signal falls back to kill() by PID. ESRCH means the PID no longer exists, so
+// there is nothing safe to fall back to.
+fn open_pidfd(pid: u32) -> Result<PidFd, PidFdError> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::FromRawFd;
+        let fd = unsafe { nix::libc::syscall(nix::libc::SYS_pidfd_open, pid as nix::libc::pid_t, 0) };
+        if fd < 0 {
+            let errno = std::io::Error::last_os_error().raw_os_error();
+            return Err(if errno == Some(nix::libc::ESRCH) {
+                PidFdError::Gone
+            } else {
+                PidFdError::Unsupported
+            });
+        }
+        Ok(unsafe { PidFd::from_raw_fd(fd as i32) })
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        Err(PidFdError::Unsupported)
+    }
+}
+
+// Without a sampled start time there is nothing to compare, so the row is
+// trusted as before.
+fn same_process(sampled: Option<u64>, current: Option<u64>) -> bool {
+    sampled.is_none() || sampled == current
+}
+
+// A pidfd pins whatever holds the PID when it is opened, which may already be a
+// reuse of the listed one. Checking the start time after opening proves the
+// pidfd refers to the sampled process; anything else is marked stale and never
+// signalled.
+fn attach_pidfds(selected: &mut [ProcessInfo], root: &Path) {
+    for proc in selected.iter_mut().filter(|p| p.pidfd.is_none() && !p.stale) {
+        match open_pidfd(proc.pid) {
+            Ok(fd) => {
+                let current = linux_stat(root, proc.pid).and_then(|stat| stat.start_ticks);
+                if same_process(proc.start_ticks, current) {
+                    proc.pidfd = Some(Arc::new(fd));
+                } else {
+                    proc.stale = true;
+                }
+            }
+            Err(PidFdError::Gone) => proc.stale = true,
+            Err(PidFdError::Unsupported) => {}
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum SignalPath {
+    Pidfd,
+    Kill,
+    Skip,
+}
+
+fn signal_path(proc: &ProcessInfo) -> SignalPath {
+    if proc.stale {
+        SignalPath::Skip
+    } else if proc.pidfd.is_some() {
+        SignalPath::Pidfd
+    } else {
+        SignalPath::Kill
+    }
+}
+
+#[cfg(unix)]
+fn send_signal(proc: &ProcessInfo, signal: Signal) -> bool {
+    match (signal_path(proc), &proc.pidfd) {
+        #[cfg(target_os = "linux")]
+        (SignalPath::Pidfd, Some(fd)) => {
+            use std::os::fd::AsRawFd;
+            let ret = unsafe {
+                nix::libc::syscall(
+                    nix::libc::SYS_pidfd_send_signal,
+                    fd.as_raw_fd(),
+                    signal as nix::libc::c_int,
+                    std::ptr::null::<nix::libc::siginfo_t>(),
+                    0,
+                )
+            };
+            ret == 0
+        }
+        (SignalPath::Skip, _) => false,
+        _ if is_pseudo_pid(proc.pid) => false,
+        _ => {
+            use nix::sys::signal::kill as nix_kill;
+            use nix::unistd::Pid as NixPid;
+            nix_kill(NixPid::from_raw(proc.pid as i32), signal).is_ok()
+        }
+    }
+}
+
+fn pid_namespace(root: &Path, pid: &str) -> Option<String> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    std::fs::read_link(root.join(pid).join("ns").join("pid"))
+        .ok()
+        .map(|link| link.to_string_lossy().to_string())
+}
+
+// Links look like `pid:[4026531836]`; an unreadable link (permissions, or the
+// process is gone) is not treated as a mismatch.
+fn foreign_namespace(own: Option<&str>, target: Option<&str>) -> bool {
+    matches!((own, target), (Some(own), Some(target)) if own != target)
+}
+
+fn kill_processes(
+    settings: &Settings,
+    selected: Vec<ProcessInfo>,
+    default_signal: Signal,
+    rules: &[(String, Signal)],
+) -> Vec<(ProcessInfo, Signal)> {
+    #[cfg(windows)]
+    let s = System::new_all();
+
+    install_stop_handler();
+    let selected = kill_targets(selected);
+    let total = selected.len();
+    let mut signalled = Vec::new();
+    let root = settings.proc_root;
+    let own_ns = pid_namespace(root, "self");
+
+    for (done, proc) in selected.into_iter().enumerate() {
+        if stop_requested(&STOP_REQUESTED) {
+            eprintln!(
+                "{} stopped after {} of {} processes",
+                Colorize::yellow("Interrupted:"),
+                done,
+                total
+            );
+            break;
+        }
+
+        if proc.stale {
+            eprintln!(
+                "{} {} {}",
+                Colorize::yellow("Skipped"),
+                Colorize::bold(proc.name.as_str()),
+                Colorize::dimmed(
+                    format!("(PID: {}, exited or replaced since it was listed)", proc.pid)
+                        .as_str()
+                )
+            );
+            continue;
+        }
+
+        let signal = signal_for(&proc, rules, default_signal);
+        if foreign_namespace(own_ns.as_deref(), pid_namespace(root, &proc.pid.to_string()).as_deref()) {
+            eprintln!(
+                "{} {} {}",
+                Colorize::yellow("Warning:"),
+                Colorize::bold(proc.name.as_str()),
+                Colorize::dimmed(
+                    format!(
+                        "(PID: {}) is in a different PID namespace; the signal may not reach the intended process",
+                        proc.pid
+                    )
+                    .as_str()
+                )
+            );
+        }
+        #[cfg(unix)]
+        let killed = send_signal(&proc, signal);
+
+        #[cfg(windows)]
+        let killed = s
+            .process(sysinfo::Pid::from_u32(proc.pid))
+            .is_some_and(|process| process.kill());
+
+        if killed {
+            let sent = if rules.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", signal_label(signal))
+            };
+            println!(
+                "{} {} {}{}",
+                Colorize::green("Killed"),
+                Colorize::bold(proc.name.as_str()),
+                Colorize::dimmed(format!("(PID: {})", proc.pid).as_str()),
+                Colorize::dimmed(sent.as_str())
+            );
+            signalled.push((proc, signal));
+        } else if settings.error_json {
+            let message = format!("failed to signal {} (PID: {})", proc.name, proc.pid);
+            report_error(settings, &message, "kill_failed");
+        } else {
+            eprintln!(
+                "{} {} {}",
+                Colorize::red("Failed"),
+                Colorize::bold(proc.name.as_str()),
+                Colorize::dimmed(format!("(PID: {})", proc.pid).as_str())
+            );
+        }
+    }
+
+    signalled
+}
+
+// Unlike --exec, an alert command is usually complete on its own, so PIDs are only
+// passed when asked for with {} (all of them, space-separated, in one run).
+fn on_match_command(template: &str, pids: &[u32]) -> Vec<String> {
+    let joined = pids
+        .iter()
+        .map(|pid| pid.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    template
+        .split_whitespace()
+        .flat_map(|word| {
+            if word == "{}" {
+                joined.split_whitespace().map(str::to_string).collect()
+            } else {
+                vec![word.replace("{}", &joined)]
+            }
+        })
+        .collect()
+}
+
+const ON_MATCH_COOLDOWN: Duration = Duration::from_secs(10);
+
+// Fires when the condition goes from false to true. A condition that flaps
+// faster than the cooldown only fires once per cooldown.
+#[derive(Debug, Default)]
+struct RisingEdge {
+    matched: bool,
+    last_fired: Option<Instant>,
+}
+
+impl RisingEdge {
+    fn update(&mut self, matched: bool, now: Instant, cooldown: Duration) -> bool {
+        let rising = matched && !self.matched;
+        self.matched = matched;
+        let cooled = self
+            .last_fired
+            .is_none_or(|last| now.duration_since(last) >= cooldown);
+        if rising && cooled {
+            self.last_fired = Some(now);
+        }
+        rising && cooled
+    }
+}
+
+fn watch_matches(mut collect: impl FnMut() -> Vec<ProcessInfo>, template: &str, interval: Duration) {
+    install_stop_handler();
+    let mut edge = RisingEdge::default();
+    while !stop_requested(&STOP_REQUESTED) {
+        let matches = collect();
+        if edge.update(!matches.is_empty(), Instant::now(), ON_MATCH_COOLDOWN) {
+            println!(
+                "{} {} process{}",
+                Colorize::yellow("Matched"),
+                matches.len(),
+                if matches.len() == 1 { "" } else { "es" }
+            );
+            let pids: Vec<u32> = matches.iter().map(|p| p.pid).collect();
+            let command = on_match_command(template, &pids);
+            if !command.is_empty() {
+                run_commands(vec![command]);
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+fn build_exec_commands(template: &str, pids: &[u32]) -> Vec<Vec<String>> {
+    let words: Vec<&str> = template.split_whitespace().collect();
+    if words.is_empty() || pids.is_empty() {
+        return Vec::new();
+    }
+    if words.iter().any(|w| w.contains("{}")) {
+        pids.iter()
+            .map(|pid| words.iter().map(|w| w.replace("{}", &pid.to_string())).collect())
+            .collect()
+    } else {
+        let mut command: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+        command.extend(pids.iter().map(|pid| pid.to_string()));
+        vec![command]
+    }
+}
+
+fn run_exec(template: &str, selected: &[ProcessInfo]) -> bool {
+    let pids: Vec<u32> = selected.iter().map(|p| p.pid).collect();
+    run_commands(build_exec_commands(template, &pids))
+}
+
+fn run_commands(commands: Vec<Vec<String>>) -> bool {
+    let mut all_ok = true;
+    for command in commands {
+        let line = command.join(" ");
+        match std::process::Command::new(&command[0]).args(&command[1..]).status() {
+            Ok(status) if status.success() => {
+                println!("{} {}", Colorize::green("Ran"), Colorize::bold(line.as_str()));
+            }
+            Ok(status) => {
+                all_ok = false;
+                eprintln!(
+                    "{} {} {}",
+                    Colorize::red("Failed"),
+                    Colorize::bold(line.as_str()),
+                    Colorize::dimmed(format!("({})", status).as_str())
+                );
+            }
+            Err(e) => {
+                all_ok = false;
+                eprintln!(
+                    "{} {} {}",
+                    Colorize::red("Failed"),
+                    Colorize::bold(line.as_str()),
+                    Colorize::dimmed(format!("({})", e).as_str())
+                );
+            }
+        }
+    }
+    all_ok
+}
+
+#[derive(Debug, PartialEq)]
+enum ZombieAction {
+    Signal,
+    Skip,
+    ReapParent(u32),
+}
+
+fn zombie_action(proc: &ProcessInfo) -> ZombieAction {
+    match (proc.zombie, proc.parent) {
+        (false, _) => ZombieAction::Signal,
+        (true, Some(ppid)) if ppid > 1 => ZombieAction::ReapParent(ppid),
+        (true, _) => ZombieAction::Skip,
+    }
+}
+
+fn handle_zombies(settings: &Settings, selected: Vec<ProcessInfo>, reap: bool) -> Vec<ProcessInfo> {
+    let mut targets = Vec::new();
+    for proc in selected {
+        let ppid = match zombie_action(&proc) {
+            ZombieAction::Signal => {
+                targets.push(proc);
+                continue;
+            }
+            ZombieAction::Skip => {
+                eprintln!(
+                    "{} {} is a zombie and can't be signalled; it has no parent to reap it",
+                    Colorize::yellow("Skipped"),
+                    display_label(&proc)
+                );
+                continue;
+            }
+            ZombieAction::ReapParent(ppid) => ppid,
+        };
+        eprintln!(
+            "{} {} is a zombie and can't be signalled; its parent (PID: {}) has to reap it",
+            Colorize::yellow("Note:"),
+            display_label(&proc),
+            ppid
+        );
+        let confirmed = reap || ask_confirm(settings, &format!("Send SIGCHLD to parent PID {}?", ppid), true);
+        if confirmed {
+            send_sigchld(ppid);
+        }
+    }
+    targets
+}
+
+fn send_sigchld(ppid: u32) {
+    #[cfg(unix)]
+    if !is_pseudo_pid(ppid) {
+        use nix::sys::signal::kill as nix_kill;
+        use nix::unistd::Pid as NixPid;
+        match nix_kill(NixPid::from_raw(ppid as i32), Signal::SIGCHLD) {
+            Ok(()) => println!(
+                "{} {}",
+                Colorize::green("Sent SIGCHLD to"),
+                Colorize::dimmed(format!("(PID: {})", ppid).as_str())
+            ),
+            Err(e) => eprintln!(
+                "{} {}",
+                Colorize::red("Failed to send SIGCHLD to"),
+                Colorize::dimmed(format!("(PID: {}): {}", ppid, e).as_str())
+            ),
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = ppid;
+}
+
+// What one pass of the kill flow over a selection ended with. The first pass
+// turns these into exit codes; --loop and --repeat rounds carry on.
+enum KillRound {
+    Empty { picked: bool, killable: bool },
+    Reaped,
+    Declined,
+    Ran { ok: bool },
+    Killed {
+        attempted: usize,
+        signalled: Vec<(ProcessInfo, Signal)>,
+        held: HashMap<u32, usize>,
+    },
+}
+
+struct KillFlow<'a> {
+    args: &'a Args,
+    shell: &'a HashSet<u32>,
+    signal: Signal,
+    rules: &'a [(String, Signal)],
+    safe: bool,
+    settings: &'a Settings,
+}
+
+impl KillFlow<'_> {
+    // Everything between picking processes and signalling them, so later --loop
+    // and --repeat rounds get the same expansion, guards and prompts as the first.
+    fn run(&self, selected: Vec<ProcessInfo>) -> KillRound {
+        let args = self.args;
+        let settings = self.settings;
+        let mut selected = selected;
+        if args.session_kill && !selected.is_empty() {
+            let before = selected.len();
+            selected = expand_sessions(selected, settings.proc_root);
+            if !args.include_self {
+                mark_self(&mut selected);
+            }
+            mark_shell(&mut selected, self.shell);
+            let mut sessions: Vec<String> = selected
+                .iter()
+                .filter_map(|p| p.session.map(|sid| sid.to_string()))
+                .collect();
+            sessions.sort();
+            sessions.dedup();
+            eprintln!(
+                "{} --session-kill expands {} selected process(es) to {} in session(s) {}",
+                Colorize::yellow("Warning:"),
+                before,
+                selected.len(),
+                sessions.join(", ")
+            );
+            if !args.confirm_nuke && !ask_confirm(settings, "Signal every process in these sessions?", false) {
+                selected.clear();
+            }
+        }
+        if args.kill_tree && !selected.is_empty() {
+            let before = selected.len();
+            selected = expand_tree(selected, settings.proc_root);
+            if !args.include_self {
+                mark_self(&mut selected);
+            }
+            mark_shell(&mut selected, self.shell);
+            let added = selected.len().saturating_sub(before);
+            if added > 0 {
+                eprintln!(
+                    "{} --kill-tree adds {} descendant process(es)",
+                    Colorize::yellow("Note:"),
+                    added
+                );
+                // Descendants nobody named by PID make the set no longer explicit.
+                let explicit = !args.confirm && explicit_pid_selection(&args.pids, &selected);
+                let unattended = args.confirm_nuke || explicit;
+                if !unattended && !ask_confirm(settings, "Signal the descendants too?", false) {
+                    selected.clear();
+                }
+            }
+        }
+        let picked = !selected.is_empty();
+        let mut selected = guard_self(selected);
+        let killable = !selected.is_empty();
+        attach_pidfds(&mut selected, settings.proc_root);
+        if args.step && !selected.is_empty() {
+            selected = step_through(selected, |proc| prompt_step(settings, proc));
+        }
+        if !confirm_danger(settings, &selected) {
+            selected.clear();
+        }
+        if selected.is_empty() {
+            return KillRound::Empty { picked, killable };
+        }
+        if args.exec.is_none() {
+            selected = handle_zombies(settings, selected, args.reap_zombies);
+            if selected.is_empty() {
+                return KillRound::Reaped;
+            }
+        }
+        // Safe mode confirms every round, even a selection named by PID.
+        if let Some(template) = &args.exec {
+            if self.safe && !confirm_exec(settings, template, &selected) {
+                return KillRound::Declined;
+            }
+            return KillRound::Ran {
+                ok: run_exec(template, &selected),
+            };
+        }
+        if self.safe && !confirm_kill(settings, &selected) {
+            return KillRound::Declined;
+        }
+
+        let held = held_ports(&selected);
+        let targets = dedupe_pids(selected);
+        let attempted = targets.len();
+        let signalled = kill_processes(settings, targets, self.signal, self.rules);
+        if !args.quiet {
+            report_survivors(&signalled);
+        }
+        KillRound::Killed {
+            attempted,
+            signalled,
+            held,
+        }
+    }
+}
+
+fn loop_continues(selected: usize, interrupted: bool) -> bool {
+    selected > 0 && !interrupted
+}
+
+// Zombies stay listed until their parent reaps them and rip itself or the
+// calling shell is never signalled, so none of them keep a --repeat going.
+fn repeat_targets(processes: Vec<ProcessInfo>) -> Vec<ProcessInfo> {
+    processes
+        .into_iter()
+        .filter(|p| !p.zombie && !p.is_self && !p.is_shell)
+        .collect()
+}
+
+fn repeat_continues(round: u32, max_rounds: u32, remaining: usize) -> bool {
+    remaining > 0 && (max_rounds == 0 || round < max_rounds)
+}
+
+fn signal_for(proc: &ProcessInfo, rules: &[(String, Signal)], default: Signal) -> Signal {
+    let name = proc.name.to_lowercase();
+    rules
+        .iter()
+        .find(|(pattern, _)| name.contains(pattern.as_str()))
+        .map(|(_, signal)| *signal)
+        .unwrap_or(default)
+}
+
+fn signal_label(signal: Signal) -> String {
+    #[cfg(unix)]
+    {
+        signal.as_str().to_string()
+    }
+    #[cfg(windows)]
+    {
+        format!("{:?}", signal).to_uppercase()
+    }
+}
+
+fn signal_terminates(signal: Signal) -> bool {
+    #[cfg(unix)]
+    {
+        matches!(
+            signal,
+            Signal::SIGKILL | Signal::SIGTERM | Signal::SIGINT | Signal::SIGHUP | Signal::SIGQUIT
+        )
+    }
+    #[cfg(windows)]
+    {
+        let _ = signal;
+        true
+    }
+}
+
+fn find_survivors<'a>(targets: &'a [ProcessInfo], alive: &HashSet<u32>) -> Vec<&'a ProcessInfo> {
+    let mut seen = HashSet::new();
+    targets
+        .iter()
+        .filter(|p| alive.contains(&p.pid) && seen.insert(p.pid))
+        .collect()
+}
+
+fn report_survivors(signalled: &[(ProcessInfo, Signal)]) {
+    let signalled: Vec<ProcessInfo> = signalled
+        .iter()
+        .filter(|(_, signal)| signal_terminates(*signal))
+        .map(|(proc, _)| proc.clone())
+        .collect();
+    if signalled.is_empty() {
+        return;
+    }
+
+    thread::sleep(Duration::from_millis(100));
+    let pids: Vec<sysinfo::Pid> = signalled
+        .iter()
+        .map(|p| sysinfo::Pid::from_u32(p.pid))
+        .collect();
+    let mut sys = System::new();
+    sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&pids), true);
+    let alive: HashSet<u32> = sys
+        .processes()
+        .iter()
+        .filter(|(_, proc)| proc.status() != sysinfo::ProcessStatus::Zombie)
+        .map(|(pid, _)| pid.as_u32())
+        .collect();
+
+    let survivors = find_survivors(&signalled, &alive);
+    if !survivors.is_empty() {
+        let names: Vec<String> = survivors
+            .iter()
+            .map(|p| format!("{} ({})", p.name, p.pid))
+            .collect();
+        eprintln!("{} {}", Colorize::yellow("Still running:"), names.join(", "));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Terminals {
+    stdin: bool,
+    stdout: bool,
+    stderr: bool,
+}
+
+impl Terminals {
+    fn detect() -> Self {
+        Terminals {
+            stdin: std::io::stdin().is_terminal(),
+            stdout: stdout().is_terminal(),
+            stderr: std::io::stderr().is_terminal(),
+        }
+    }
+}
+
+// Modes that print what matched and exit without signalling anything.
+fn lists_only(args: &Args) -> bool {
+    args.names
+        || args.write_pids.is_some()
+        || args.output.is_some()
+        || args.histogram
+        || args.group_by.is_some()
+}
+
+fn has_kill_option(args: &Args) -> bool {
+    args.confirm_nuke
+        || args.kill_oldest
+        || args.kill_newest
+        || args.kill_tree
+        || args.session_kill
+        || args.loop_selector
+        || args.repeat
+        || args.step
+        || args.reap_zombies
+        || !args.pids.is_empty()
+        || args.exec.is_some()
+        || args.on_match.is_some()
+}
+
+// The live TUI draws on stdout and the inquire prompts on stderr; both read keys from stdin.
+fn missing_tty(args: &Args, safe: bool, terminals: Terminals) -> Option<&'static str> {
+    let listing = lists_only(args) || !args.diff.is_empty();
+    if listing || args.watch_port.is_some() || args.on_match.is_some() {
+        return None;
+    }
+    if args.live {
+        return (!terminals.stdin || !terminals.stdout).then_some("live mode");
+    }
+    let unattended = args.confirm_nuke || (!args.pids.is_empty() && !args.confirm);
+    let prompts = args.step || safe || !unattended;
+    (prompts && (!terminals.stdin || !terminals.stderr)).then_some("the interactive selector")
+}
+
+fn validate_args(args: &Args) -> Result<(), String> {
+    if args.show_fg && !cfg!(target_os = "linux") {
+        eprintln!("{}", Colorize::yellow("Warning: --show-fg is only available on Linux; showing \"-\"."));
+    }
+    if let Some(hash) = &args.exe_hash {
+        if !cfg!(target_os = "linux") {
+            return Err("Error: --exe-hash is only supported on Linux.".to_string());
+        }
+        if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err("Error: --exe-hash expects a 64-character hex SHA-256 digest.".to_string());
+        }
+    }
+    if args.has_open.is_some() && !cfg!(target_os = "linux") {
+        return Err("Error: --has-open is only supported on Linux.".to_string());
+    }
+    if (args.show_prio || args.min_prio.is_some() || args.max_prio.is_some())
+        && !cfg!(target_os = "linux")
+    {
+        return Err("Error: --show-prio, --min-prio and --max-prio are only supported on Linux.".to_string());
+    }
+    if let (Some(min), Some(max)) = (args.min_prio, args.max_prio) {
+        if min > max {
+            return Err(format!("Error: --min-prio {} is above --max-prio {}.", min, max));
+        }
+    }
+    if (args.only_with_tty || args.no_tty) && !cfg!(target_os = "linux") {
+        return Err("Error: --only-with-tty and --no-tty are only supported on Linux.".to_string());
+    }
+    if args.cpu_window.is_some() && !cfg!(target_os = "linux") {
+        return Err("Error: --cpu-window is only supported on Linux.".to_string());
+    }
+    if args.session_kill && !cfg!(target_os = "linux") {
+        return Err("Error: --session-kill is only supported on Linux.".to_string());
+    }
+    if (args.session.is_some() || args.current_session) && !cfg!(target_os = "linux") {
+        return Err("Error: --session and --current-session are only supported on Linux.".to_string());
+    }
+    if let Some(path) = &args.proc_path {
+        if !cfg!(target_os = "linux") {
+            return Err("Error: --proc-path is only supported on Linux.".to_string());
+        }
+        if args.live || args.ports || port_filter(args).is_some() || !args.listening.is_empty() {
+            return Err("Error: --proc-path cannot be combined with --live, --ports, --port, --port-range or --listening; live CPU and socket data are only available for the host procfs.".to_string());
+        }
+        if !lists_only(args) || has_kill_option(args) {
+            return Err("Error: --proc-path is inspection-only; use it with --names, --write-pids, --output, --group-by or --histogram, without kill options.".to_string());
+        }
+        if !path.is_dir() {
+            return Err(format!("Error: --proc-path {} is not a directory.", path.display()));
+        }
+    }
+    if args.exec.as_deref().is_some_and(|t| t.trim().is_empty()) {
+        return Err("Error: --exec needs a command to run.".to_string());
+    }
+    if let Some(Err(e)) = args.query.as_deref().map(parse_query) {
+        return Err(format!("Error: invalid --query: {}", e));
+    }
+    if args.watch_port.is_some() && !args.until_free && !args.until_taken {
+        return Err("Error: --watch-port needs --until-free or --until-taken.".to_string());
+    }
+    if args.repeat && !has_match_filter(args) {
+        return Err("Error: --repeat requires a filter (-f, --filter, --query) or a port (--port, --port-range, --listening); respawned processes get new PIDs.".to_string());
+    }
+    if args.confirm_nuke && !has_match_filter(args) {
+        return Err("Error: Process --confirm-nuke requires a filter (-f, --filter, --query) or a port (--port, --port-range, --listening) to prevent accidental mass deletion.".to_string());
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut matches = Args::command().try_get_matches().unwrap_or_else(|e| clap_exit(e));
+    let settings = Settings {
+        error_json: matches.get_flag("error_json"),
+        ..Settings::default()
+    };
+    let mut config = load_config().unwrap_or_else(|e| fail(&settings, &e, "config"));
+    if let Some(name) = matches.get_one::<String>("profile") {
+        match profile_argv(&config, name, std::env::args_os().collect()) {
+            Ok(argv) => {
+                matches = Args::command()
+                    .args_override_self(true)
+                    .try_get_matches_from(argv)
+                    .unwrap_or_else(|e| clap_exit(e))
+            }
+            Err(e) => fail(&settings, &e, "unknown_profile"),
+        }
+    }
+    let mut args = match Args::from_arg_matches(&matches) {
+        Ok(args) => args,
+        Err(e) => clap_exit(e),
+    };
+
+    if let Err(e) = validate_args(&args) {
+        fail(&settings, &e, "invalid_args");
+    }
+
+    let signal = parse_signal(&args.signal)
+        .unwrap_or_else(|e| fail(&settings, &format!("Error: {}", e), "invalid_signal"));
+
+    config.danger_ports.extend(&args.danger_ports);
+    let safe = args.safe || config.safe;
+    if let Some(e) = safe_mode_error(&args, safe) {
+        fail(&settings, &e, "safe_mode");
+    }
+    if matches.value_source("interval") == Some(ValueSource::DefaultValue) {
+        args.interval = default_interval_ms(running_over_ssh());
+    }
+    if matches.value_source("sort") == Some(ValueSource::DefaultValue) {
+        if let Some(sort) = config.sort {
+            args.sort = sort;
+        }
+    }
+    if let Some((sort, limit)) = top_shortcut(args.top_cpu, args.top_mem) {
+        args.sort = sort;
+        args.limit = Some(limit);
+    }
+    if let Some(spec) = args.user.take() {
+        let users = Users::new_with_refreshed_list();
+        args.user = Some(resolve_user(&spec, &users).unwrap_or_else(|e| fail(&settings, &e, "unknown_user")));
+    }
+
+    let port_filter = port_filter(&args);
+    let ports_mode = args.ports
+        || port_filter.is_some()
+        || !args.listening.is_empty()
+        || args.by_port
+        || args.public_only;
+
+    if args.theme == ThemeName::Mono {
+        colored::control::set_override(false);
+    }
+    let terminals = Terminals::detect();
+    let dumb = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+    // Both strings live for the whole run; leaking them keeps Settings Copy.
+    let settings = Settings {
+        proc_root: match &args.proc_path {
+            Some(path) => Box::leak(path.clone().into_boxed_path()),
+            None => settings.proc_root,
+        },
+        mem_kind: args.mem_kind,
+        gap: Box::leak(column_gap(&args.separator, args.pad).into_boxed_str()),
+        confirm_style: resolve_confirm_style(args.confirm_style, terminals, dumb),
+        theme: Theme::named(args.theme),
+        ..settings
+    };
+
+    if let Some(mode) = missing_tty(&args, safe, terminals) {
+        fail(&settings, 
+            &format!(
+                "Error: {} needs a terminal. Use --names or --write-pids to list matches, or -f/--port with --confirm-nuke to kill without prompting.",
+                mode
+            ),
+            "no_tty",
+        );
+    }
+
+    if let [old, new] = args.diff.as_slice() {
+        let old = load_snapshot(old).unwrap_or_else(|e| fail(&settings, &e, "snapshot"));
+        let new = load_snapshot(new).unwrap_or_else(|e| fail(&settings, &e, "snapshot"));
+        print_snapshot_diff(&diff_snapshots(&old, &new));
+        return;
+    }
+
+    if let Some(group_by) = args.group_by {
+        let mut processes = sample_processes(&args, &settings);
+        enrich_processes(&mut processes, settings.proc_root);
+        apply_filters(&mut processes, &args, settings.proc_root);
+        match group_by {
+            GroupBy::User => {
+                print_group_summary(&group_by_user(&processes, args.sort), "user", settings.mem_kind)
+            },
+        }
+        return;
+    }
+
+    if let Some(port) = args.watch_port {
+        wait_for_port(port, args.until_free, Duration::from_millis(args.interval));
+        return;
+    }
+
+    if args.histogram {
+        let mut processes = sample_processes(&args, &settings);
+        enrich_processes(&mut processes, settings.proc_root);
+        apply_filters(&mut processes, &args, settings.proc_root);
+        let cpu = bucket_counts(processes.iter().map(|p| p.cpu), &CPU_BUCKETS);
+        let memory = bucket_counts(processes.iter().map(|p| p.memory / MB), &MEM_BUCKETS);
+        print_histogram("CPU", &CPU_BUCKET_LABELS, &cpu);
+        println!();
+        print_histogram("Memory", &MEM_BUCKET_LABELS, &memory);
+        return;
+    }
+
+    if args.live {
+        let (attempted, signalled) = run_live_mode(&args, &config, signal, &settings)
+            .unwrap_or_else(|e| fail(&settings, &format!("Error: {}", e), "live"));
+        let code = kill_exit_code(attempted, signalled, stop_requested(&STOP_REQUESTED));
+        if code != 0 {
+            std::process::exit(code);
+        }
+        return;
+    }
+
+    let mut sampler = ProcessSampler::new(needs_cpu(&args), settings.mem_kind);
+    let mut fetch = |filter: Option<&str>| {
+        if args.by_port {
+            sampler.port_rows(filter, port_filter)
+        } else if ports_mode {
+            sampler.processes_with_ports(filter, port_filter, args.sort, args.show_orphan_ports)
+        } else if args.proc_path.is_some() {
+            read_proc_path(&settings, filter, args.sort)
+        } else {
+            sampler.processes(filter, args.sort)
+        }
+    };
+    // rip's own ancestry lives in the host procfs, never under --proc-path.
+    let shell = if args.force || args.proc_path.is_some() {
+        HashSet::new()
+    } else {
+        shell_pids(settings.proc_root)
+    };
+    let mut collect = || {
+        let mut processes = fetch(args.filter.as_deref());
+        if let Some(digits) = smart_pid_filter(&args).filter(|_| processes.is_empty()) {
+            processes = fetch(None);
+            processes.retain(|p| pid_matches(p.pid, digits));
+        }
+        if args.sum_thread_cpu {
+            processes = merge_thread_cpu(processes);
+            sort_processes(&mut processes, args.sort);
+        }
+        enrich_processes(&mut processes, settings.proc_root);
+        if args.show_sockets || args.sort == SortBy::Sockets {
+            count_sockets(&mut processes, settings.proc_root);
+        }
+        if args.show_fd_usage || args.sort == SortBy::FdPct {
+            fill_fd_usage(&mut processes, settings.proc_root);
+        }
+        if !args.pin.is_empty() {
+            mark_pinned(&mut processes, &args.pin);
+        }
+        if sorts_after_enrich(args.sort) || !args.pin.is_empty() {
+            sort_processes(&mut processes, args.sort);
+        }
+        apply_filters(&mut processes, &args, settings.proc_root);
+        if args.dedupe_by == Some(DedupeBy::Name) {
+            processes = dedupe_by_name(processes);
+        }
+        if args.shuffle {
+            shuffle_processes(&mut processes, shuffle_seed());
+        }
+        if let Some(limit) = args.limit {
+            processes.truncate(limit);
+        }
+        if args.tree {
+            processes = tree_order(processes, args.show_spawn_order);
+        }
+        if !args.include_self {
+            mark_self(&mut processes);
+        }
+        mark_shell(&mut processes, &shell);
+        mark_danger(&mut processes, &config.danger_ports);
+        let mut columns = Columns::from_args(&args, settings);
+        if ports_mode && !args.always_show_proto {
+            columns.single_protocol = single_protocol(&processes);
+        }
+        apply_columns(&mut processes, columns, ports_mode);
+        processes
+    };
+    if let Some(template) = &args.on_match {
+        watch_matches(&mut collect, template, Duration::from_millis(args.interval));
+        return;
+    }
+    let processes = collect();
+
+    if let Some(OutputFormat::Tsv) = args.output {
+        let header = args.tsv_header.then_some("#");
+        print!("{}", snapshot_tsv(&processes, header));
+        return;
+    }
+
+    if args.names || args.write_pids.is_some() {
+        let (path, entries): (Option<&Path>, Vec<String>) = if args.names {
+            (None, processes.iter().map(|p| p.name.clone()).collect())
+        } else {
+            (
+                args.write_pids.as_deref(),
+                processes.iter().map(|p| p.pid.to_string()).collect(),
+            )
+        };
+        if let Err(e) = write_entries(path, &entries, args.null) {
+            fail(&settings, &format!("Error: {}", e), "write");
+        }
+        if entries.is_empty() {
+            std::process::exit(EXIT_NO_MATCH);
+        }
+        return;
+    }
+
+    let missing = missing_pids(&args.pids, &processes);
+    for pid in &missing {
+        report_error(&settings, &format!("Error: no matching process with PID {}", pid), "no_such_pid");
+    }
+
+    if processes.is_empty() {
+        if ports_mode {
+            println!("No processes with listening ports found");
+        } else {
+            println!("No processes found");
+        }
+        std::process::exit(EXIT_NO_MATCH);
+    }
+
+    let explicit = !args.confirm && explicit_pid_selection(&args.pids, &processes);
+    let selected = if args.kill_oldest || args.kill_newest {
+        match pick_by_age(&processes, args.kill_oldest) {
+            Some(proc) if args.confirm_nuke || explicit || confirm_single(&settings, &proc) => vec![proc],
+            _ => vec![],
+        }
+    } else if args.confirm_nuke || explicit {
+        processes
+    } else {
+        if args.summary {
+            eprintln!("{}", Colorize::dimmed(selection_summary(&processes).as_str()));
+        }
+        run_selector(selector_order(processes, args.selector_sort), ports_mode, args.minimal)
+    };
+
+    let flow = KillFlow {
+        args: &args,
+        shell: &shell,
+        signal,
+        rules: &config.signal_rules,
+        safe,
+        settings: &settings,
+    };
+    let (attempted, signalled, held) = match flow.run(selected) {
+        KillRound::Empty { picked, killable } => {
+            println!("No processes selected");
+            std::process::exit(empty_selection_code(picked, killable));
+        }
+        KillRound::Reaped => return,
+        KillRound::Declined => {
+            println!("No processes selected");
+            std::process::exit(EXIT_ABORTED);
+        }
+        KillRound::Ran { ok } => {
+            if !ok {
+                std::process::exit(EXIT_ERROR);
+            }
+            return;
+        }
+        KillRound::Killed {
+            attempted,
+            signalled,
+            held,
+        } => (attempted, signalled, held),
+    };
+    let mut code = kill_exit_code(attempted, signalled.len(), stop_requested(&STOP_REQUESTED));
+    if ports_mode && !held.is_empty() {
+        let ports: usize = signalled.iter().filter_map(|(p, _)| held.get(&p.pid)).sum();
+        println!(
+            "Signalled {} process{} holding {} port{}",
+            signalled.len(),
+            if signalled.len() == 1 { "" } else { "es" },
+            ports,
+            if ports == 1 { "" } else { "s" }
+        );
+    }
+
+    let mut selected_count = attempted;
+    while args.loop_selector && loop_continues(selected_count, stop_requested(&STOP_REQUESTED)) {
+        let processes = collect();
+        if processes.is_empty() {
+            break;
+        }
+        let selected =
+            run_selector(selector_order(processes, args.selector_sort), ports_mode, args.minimal);
+        selected_count = selected.len();
+        if let KillRound::Killed {
+            attempted,
+            signalled,
+            ..
+        } = flow.run(selected)
+        {
+            let interrupted = stop_requested(&STOP_REQUESTED);
+            code = code.max(kill_exit_code(attempted, signalled.len(), interrupted));
+        }
+    }
+
+    if args.repeat {
+        println!("Round 1: killed {}", signalled.len());
+        let mut round = 1;
+        while !stop_requested(&STOP_REQUESTED) {
+            thread::sleep(Duration::from_millis(args.repeat_interval));
+            if stop_requested(&STOP_REQUESTED) {
+                code = code.max(EXIT_ABORTED);
+                break;
+            }
+            let targets = repeat_targets(collect());
+            if !repeat_continues(round, args.repeat_max, targets.len()) {
+                if targets.is_empty() {
+                    println!("No matching processes left after {} round(s)", round);
+                } else {
+                    code = code.max(EXIT_KILL_FAILED);
+                    eprintln!(
+                        "{} {} matching process(es) still running after {} rounds",
+                        Colorize::yellow("Giving up:"),
+                        targets.len(),
+                        round
+                    );
+                }
+                break;
+            }
+            round += 1;
+            let KillRound::Killed {
+                attempted,
+                signalled,
+                ..
+            } = flow.run(targets)
+            else {
+                code = code.max(EXIT_ABORTED);
+                break;
+            };
+            println!("Round {}: killed {}", round, signalled.len());
+            let interrupted = stop_requested(&STOP_REQUESTED);
+            code = code.max(kill_exit_code(attempted, signalled.len(), interrupted));
+        }
+    }
+    if code == 0 && !missing.is_empty() {
+        code = EXIT_KILL_FAILED;
+    }
+    if code != 0 {
+        std::process::exit(code);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proc(pid: u32, name: &str) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reconcile_selection_drops_exited_pids() {
+        let mut selected = HashSet::from([1, 2, 3]);
+        let exited = reconcile_selection(&mut selected, &[proc(1, "a"), proc(3, "c")]);
+        assert_eq!(exited, vec![2]);
+        assert_eq!(selected, HashSet::from([1, 3]));
+    }
+
+    #[test]
+    fn reconcile_selection_reports_exits_in_pid_order() {
+        let mut selected = HashSet::from([9, 4, 7]);
+        assert_eq!(reconcile_selection(&mut selected, &[]), vec![4, 7, 9]);
+        assert!(selected.is_empty());
+        assert!(reconcile_selection(&mut selected, &[proc(1, "a")]).is_empty());
+    }
+
+    fn owned(pid: u32, owner: Option<&str>, cpu: f32, memory: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            owner: owner.map(str::to_string),
+            cpu,
+            memory,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn group_by_user_sums_each_owner() {
+        let processes = [
+            owned(1, Some("alice"), 10.0, 100),
+            owned(2, Some("bob"), 5.0, 300),
+            owned(3, Some("alice"), 2.5, 50),
+            owned(4, None, 1.0, 10),
+        ];
+        let summary = group_by_user(&processes, SortBy::Cpu);
+        let alice = GroupSummary {
+            key: "alice".to_string(),
+            count: 2,
+            cpu: 12.5,
+            memory: 150,
+        };
+        assert_eq!(summary[0], alice);
+        assert_eq!(summary.len(), 3);
+        assert!(summary.iter().any(|g| g.key == "?" && g.count == 1));
+    }
+
+    #[test]
+    fn group_by_user_orders_by_the_chosen_sort() {
+        let processes = [
+            owned(1, Some("alice"), 10.0, 100),
+            owned(2, Some("bob"), 5.0, 300),
+            owned(3, Some("carol"), 1.0, 10),
+            owned(4, Some("carol"), 1.0, 10),
+            owned(5, Some("carol"), 1.0, 10),
+        ];
+        let keys = |sort| -> Vec<String> {
+            group_by_user(&processes, sort)
+                .into_iter()
+                .map(|g| g.key)
+                .collect()
+        };
+        assert_eq!(keys(SortBy::Mem), ["bob", "alice", "carol"]);
+        assert_eq!(keys(SortBy::Name), ["alice", "bob", "carol"]);
+        assert_eq!(keys(SortBy::Pid)[0], "carol");
+    }
+
+    #[test]
+    fn mark_self_annotates_the_rip_row() {
+        let mut rows = vec![proc(std::process::id(), "rip"), proc(1, "init")];
+        mark_self(&mut rows);
+        assert!(rows[0].is_self);
+        assert!(!rows[1].is_self);
+        assert_eq!(display_label(&rows[0]), "rip (this is rip)");
+        assert_eq!(display_label(&rows[1]), "init");
+    }
+
+    #[test]
+    fn guard_self_drops_rip_and_the_shell() {
+        let mut rows = vec![proc(10, "rip"), proc(11, "bash"), proc(12, "node")];
+        rows[0].is_self = true;
+        rows[1].is_shell = true;
+        assert_eq!(display_label(&rows[1]), "bash (your shell)");
+        let kept: Vec<u32> = guard_self(rows).iter().map(|p| p.pid).collect();
+        assert_eq!(kept, vec![12]);
+    }
+
+    #[test]
+    fn adaptive_interval_halves_on_volatile_cpu() {
+        let current = Duration::from_millis(2000);
+        assert_eq!(
+            adjust_refresh_interval(current, 25.0),
+            Duration::from_millis(1000)
+        );
+        assert_eq!(
+            adjust_refresh_interval(ADAPTIVE_MIN_INTERVAL, 25.0),
+            ADAPTIVE_MIN_INTERVAL
+        );
+    }
+
+    #[test]
+    fn adaptive_interval_backs_off_when_cpu_is_steady() {
+        let current = Duration::from_millis(2000);
+        assert_eq!(
+            adjust_refresh_interval(current, 0.5),
+            Duration::from_millis(3000)
+        );
+        assert_eq!(adjust_refresh_interval(current, 5.0), current);
+        assert_eq!(
+            adjust_refresh_interval(ADAPTIVE_MAX_INTERVAL, 0.0),
+            ADAPTIVE_MAX_INTERVAL
+        );
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rip-test-{}-{}", std::process::id(), name))
+    }
+
+    fn write_fixture_process(root: &Path, pid: u32, stat: &str, status: &str, cmdline: &[u8]) {
+        let dir = root.join(pid.to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("stat"), stat).unwrap();
+        std::fs::write(dir.join("status"), status).unwrap();
+        std::fs::write(dir.join("cmdline"), cmdline).unwrap();
+    }
+
+    #[test]
+    fn get_processes_from_proc_reads_a_fixture_directory() {
+        let root = temp_path("procfs");
+        let _ = std::fs::remove_dir_all(&root);
+        write_fixture_process(
+            &root,
+            100,
+            "100 (server) S 1 100 100 0 -1 0 0 0 0 0 5 5 0 0 20 0 1 0 500 0 0",
+            "Name:\tserver\nVmRSS:\t  2048 kB\nVmSize:\t 8192 kB\nUid:\t4242\t4242\t4242\t4242\n",
+            b"/usr/bin/server\0--port\08080\0",
+        );
+        write_fixture_process(
+            &root,
+            101,
+            "101 (worker) Z 100 100 100 0 -1 0 0 0 0 0 0 0 0 0 20 0 1 0 600 0 0",
+            "Name:\tworker\n",
+            b"",
+        );
+        std::fs::create_dir_all(root.join("self")).unwrap();
+
+        let rows = get_processes_from_proc(&root, None, SortBy::Pid, MemKind::Rss).unwrap();
+        let filtered = get_processes_from_proc(&root, Some("SERV"), SortBy::Pid, MemKind::Rss).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            rows.iter().map(|p| p.pid).collect::<Vec<_>>(),
+            vec![100, 101]
+        );
+        let server = &rows[0];
+        assert_eq!(server.name, "server");
+        assert_eq!(server.memory, 2048 * 1024);
+        assert_eq!(server.cmd, ["/usr/bin/server", "--port", "8080"]);
+        assert_eq!(server.child_count, 1);
+        assert!(server.owner.is_some());
+        let worker = &rows[1];
+        assert!(worker.zombie);
+        assert_eq!(worker.parent, Some(100));
+        assert_eq!(worker.memory, 0);
+        assert_eq!(filtered.len(), 1);
+    }
+
+    #[test]
+    fn parse_status_field_finds_keys_exactly() {
+        let status =
+            "Name:\tnode\nVmRSS:\t  1234 kB\nVmRSSX:\t9 kB\nUid:\t1000\t1000\t1000\t1000\n";
+        assert_eq!(parse_status_field(status, "VmRSS"), Some("1234 kB"));
+        assert_eq!(
+            parse_status_field(status, "Uid"),
+            Some("1000\t1000\t1000\t1000")
+        );
+        assert_eq!(parse_status_field(status, "Vm"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn proc_path_is_inspection_only() {
+        let root = std::env::temp_dir();
+        let check = |extra: &[&str]| {
+            let mut argv = vec!["rip", "--proc-path", root.to_str().unwrap()];
+            argv.extend(extra);
+            validate_args(&Args::try_parse_from(argv).unwrap())
+        };
+        assert!(check(&["--names"]).is_ok());
+        assert!(check(&["--group-by", "user"]).is_ok());
+        assert!(check(&["--histogram"]).is_ok());
+        assert!(check(&[]).is_err());
+        assert!(check(&["-f", "node", "--confirm-nuke"]).is_err());
+        assert!(check(&["--names", "--pid", "42"]).is_err());
+        assert!(check(&["--write-pids", "pids.txt", "--kill-tree"]).is_err());
+        assert!(check(&["--output", "tsv", "--exec", "echo {}"]).is_err());
+    }
+
+    #[test]
+    fn parse_step_answer_accepts_short_and_long_forms() {
+        assert_eq!(parse_step_answer("y"), StepDecision::Yes);
+        assert_eq!(parse_step_answer(" YES \n"), StepDecision::Yes);
+        assert_eq!(parse_step_answer("a"), StepDecision::All);
+        assert_eq!(parse_step_answer("quit"), StepDecision::Quit);
+        assert_eq!(parse_step_answer("n"), StepDecision::No);
+        assert_eq!(parse_step_answer(""), StepDecision::No);
+    }
+
+    fn step_pids(answers: &[StepDecision]) -> (Vec<u32>, usize) {
+        let rows = vec![proc(1, "a"), proc(2, "b"), proc(3, "c"), proc(4, "d")];
+        let mut asked = 0;
+        let kept = step_through(rows, |_| {
+            asked += 1;
+            answers[asked - 1]
+        });
+        (kept.iter().map(|p| p.pid).collect(), asked)
+    }
+
+    #[test]
+    fn step_through_keeps_yes_and_skips_no() {
+        use StepDecision::*;
+        assert_eq!(step_pids(&[Yes, No, No, Yes]), (vec![1, 4], 4));
+    }
+
+    #[test]
+    fn step_through_all_takes_the_rest_without_asking() {
+        use StepDecision::*;
+        assert_eq!(step_pids(&[No, All]), (vec![2, 3, 4], 2));
+    }
+
+    #[test]
+    fn step_through_quit_kills_nothing() {
+        use StepDecision::*;
+        assert_eq!(step_pids(&[Yes, Yes, Quit]), (vec![], 3));
+    }
+
+    fn on_port(pid: u32, port: u16) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            port: Some(port),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mark_danger_flags_listed_ports_only() {
+        let mut rows = vec![on_port(1, 22), on_port(2, 8080), proc(3, "no-port")];
+        mark_danger(&mut rows, &[22, 3306]);
+        assert_eq!(
+            rows.iter().map(|p| p.danger).collect::<Vec<_>>(),
+            [true, false, false]
+        );
+    }
+
+    #[test]
+    fn danger_ports_in_lists_each_port_once() {
+        let mut rows = vec![
+            on_port(1, 3306),
+            on_port(2, 22),
+            on_port(3, 22),
+            on_port(4, 80),
+        ];
+        mark_danger(&mut rows, &[22, 3306]);
+        assert_eq!(danger_ports_in(&rows), vec![22, 3306]);
+        assert!(danger_ports_in(&rows[3..]).is_empty());
+    }
+
+    #[test]
+    fn config_danger_ports_default_to_ssh_and_can_be_replaced() {
+        assert_eq!(Config::default().danger_ports, vec![22]);
+        let config = parse_config("danger_ports = [22, 5432]\n").unwrap();
+        assert_eq!(config.danger_ports, vec![22, 5432]);
+        assert!(parse_config("danger_ports = [70000]\n").is_err());
+        assert!(parse_config("danger_ports = [\"ssh\"]\n").is_err());
+    }
+
+    #[test]
+    fn child_counts_from_a_synthetic_parent_map() {
+        let parents = HashMap::from([(2, 1), (3, 1), (4, 2), (5, 1)]);
+        let counts = child_counts(&parents);
+        assert_eq!(counts.get(&1), Some(&3));
+        assert_eq!(counts.get(&2), Some(&1));
+        assert_eq!(counts.get(&4), None);
+    }
+
+    #[test]
+    fn sort_by_children_puts_busy_parents_first() {
+        let mut rows: Vec<ProcessInfo> = [(1, 0), (2, 5), (3, 2)]
+            .into_iter()
+            .map(|(pid, child_count)| ProcessInfo {
+                pid,
+                child_count,
+                ..Default::default()
+            })
+            .collect();
+        sort_processes(&mut rows, SortBy::Children);
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [2, 3, 1]);
+    }
+
+    #[test]
+    fn stop_requested_reads_the_flag() {
+        let flag = AtomicBool::new(false);
+        assert!(!stop_requested(&flag));
+        flag.store(true, Ordering::SeqCst);
+        assert!(stop_requested(&flag));
+    }
+
+    #[test]
+    fn kill_loop_signals_nothing_once_stop_is_requested() {
+        // Far above any pid_max, so nothing could be signalled even if the check failed.
+        let target = proc(999_999_999, "ghost");
+        STOP_REQUESTED.store(true, Ordering::SeqCst);
+        let signalled = kill_processes(&Settings::default(), vec![target], Signal::SIGTERM, &[]);
+        STOP_REQUESTED.store(false, Ordering::SeqCst);
+        assert!(signalled.is_empty());
+        assert_eq!(kill_exit_code(1, signalled.len(), true), EXIT_ABORTED);
+    }
+
+    fn binding(port: u16, protocol: &str) -> PortBinding {
+        PortBinding {
+            port,
+            protocol: protocol.to_string(),
+            addr: IpAddr::from([0, 0, 0, 0]),
+            ipv4: true,
+            ipv6: false,
+        }
+    }
+
+    #[test]
+    fn build_port_rows_pivots_on_port() {
+        let port_map: PortMap = HashMap::from([
+            (10, vec![binding(8080, "TCP"), binding(53, "UDP")]),
+            (20, vec![binding(53, "TCP")]),
+            (30, vec![binding(22, "TCP")]),
+        ]);
+        let names = HashMap::from([(10, "dev-server".to_string()), (20, "dnsmasq".to_string())]);
+        let rows = build_port_rows(&port_map, &names, None, None);
+        let pivot: Vec<(Option<u16>, u32, &str)> = rows
+            .iter()
+            .map(|r| (r.port, r.pid, r.name.as_str()))
+            .collect();
+        assert_eq!(
+            pivot,
+            [
+                (Some(22), 30, "?"),
+                (Some(53), 20, "dnsmasq"),
+                (Some(53), 10, "dev-server"),
+                (Some(8080), 10, "dev-server"),
+            ]
+        );
+        assert_eq!(rows[1].protocol.as_deref(), Some("TCP"));
+        assert_eq!(rows[0].stack, "v4");
+    }
+
+    #[test]
+    fn build_port_rows_applies_name_and_port_filters() {
+        let port_map: PortMap = HashMap::from([
+            (10, vec![binding(8080, "TCP"), binding(9000, "TCP")]),
+            (20, vec![binding(8081, "TCP")]),
+        ]);
+        let names = HashMap::from([(10, "Node".to_string()), (20, "python".to_string())]);
+        let range = PortRange {
+            start: 8000,
+            end: 8999,
+        };
+        let rows = build_port_rows(&port_map, &names, Some("node"), Some(range));
+        assert_eq!(
+            rows.iter().map(|r| r.port).collect::<Vec<_>>(),
+            [Some(8080)]
+        );
+    }
+
+    #[test]
+    fn truncate_strategies_on_a_long_path() {
+        let path = "/opt/app/bin/server.py";
+        assert_eq!(truncate_with(path, 12, Truncate::End), "/opt/app/...");
+        assert_eq!(truncate_with(path, 12, Truncate::Start), "...server.py");
+        assert_eq!(truncate_with(path, 12, Truncate::Middle), "/opt/...r.py");
+        assert_eq!(truncate(path, 12), truncate_with(path, 12, Truncate::End));
+    }
+
+    #[test]
+    fn truncate_exact_fit_is_unchanged() {
+        for strategy in [Truncate::End, Truncate::Start, Truncate::Middle] {
+            assert_eq!(truncate_with("server", 6, strategy), "server");
+        }
+    }
+
+    #[test]
+    fn truncate_very_short_widths_cut_without_ellipsis() {
+        for strategy in [Truncate::End, Truncate::Start, Truncate::Middle] {
+            assert_eq!(truncate_with("server", 3, strategy), "ser");
+            assert_eq!(truncate_with("server", 1, strategy), "s");
+            assert_eq!(truncate_with("server", 0, strategy), "");
+        }
+        assert_eq!(truncate_with("server", 4, Truncate::Start), "...r");
+        assert_eq!(truncate_with("server", 4, Truncate::Middle), "s...");
+    }
+
+    #[test]
+    fn refresh_limit_zero_runs_forever() {
+        assert!(!refresh_limit_reached(0, 0));
+        assert!(!refresh_limit_reached(1_000_000, 0));
+    }
+
+    #[test]
+    fn refresh_limit_stops_after_n_refreshes() {
+        let limit = 3;
+        let mut refreshes = 0;
+        let mut stopped_at = None;
+        for _ in 0..10 {
+            refreshes += 1;
+            if refresh_limit_reached(refreshes, limit) {
+                stopped_at = Some(refreshes);
+                break;
+            }
+        }
+        assert_eq!(stopped_at, Some(3));
+    }
+
+    fn in_session(pid: u32, session: Option<u32>) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            session,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn session_matches_only_the_target_sid() {
+        assert!(session_matches(Some(7), 7));
+        assert!(!session_matches(Some(8), 7));
+        assert!(!session_matches(None, 7));
+    }
+
+    #[test]
+    fn session_filter_keeps_matching_processes() {
+        let args = Args::try_parse_from(["rip", "--session", "7"]).unwrap();
+        assert_eq!(session_target(&args), Some(7));
+        let mut rows = vec![
+            in_session(1, Some(7)),
+            in_session(2, Some(9)),
+            in_session(3, None),
+            in_session(4, Some(7)),
+        ];
+        apply_filters(&mut rows, &args, Path::new("/proc"));
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [1, 4]);
+    }
+
+    #[test]
+    fn parse_proc_stat_extracts_tpgid_for_the_foreground_check() {
+        let fg =
+            parse_proc_stat("300 (vim) S 200 300 200 34817 300 0 0 0 0 0 1 1 0 0 20 0").unwrap();
+        assert_eq!((fg.pgrp, fg.tty_nr, fg.tpgid), (300, 34817, 300));
+        assert!(is_foreground(&fg));
+        let bg =
+            parse_proc_stat("301 (make) S 200 301 200 34817 300 0 0 0 0 0 1 1 0 0 20 0").unwrap();
+        assert!(!is_foreground(&bg));
+        let daemon = parse_proc_stat("5 (sshd) S 1 5 5 0 -1 0 0 0 0 0 1 1 0 0 20 0").unwrap();
+        assert_eq!(daemon.tpgid, -1);
+        assert!(!is_foreground(&daemon));
+    }
+
+    #[test]
+    fn foreground_label_falls_back_to_dash() {
+        assert_eq!(foreground_label(Some(true)), "fg");
+        assert_eq!(foreground_label(Some(false)), "bg");
+        assert_eq!(foreground_label(None), "-");
+    }
+
+    #[test]
+    fn find_survivors_lists_targets_still_alive_once() {
+        let targets = vec![proc(1, "a"), proc(2, "b"), proc(2, "b"), proc(3, "c")];
+        let alive = HashSet::from([2, 3, 99]);
+        let survivors: Vec<u32> = find_survivors(&targets, &alive)
+            .iter()
+            .map(|p| p.pid)
+            .collect();
+        assert_eq!(survivors, vec![2, 3]);
+        assert!(find_survivors(&targets, &HashSet::new()).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_terminating_signals_are_checked_for_survivors() {
+        assert!(signal_terminates(Signal::SIGTERM));
+        assert!(signal_terminates(Signal::SIGKILL));
+        assert!(!signal_terminates(Signal::SIGSTOP));
+        assert!(!signal_terminates(Signal::SIGUSR1));
+    }
+
+    #[test]
+    fn selector_order_resorts_only_when_asked() {
+        let mut rows = vec![proc(3, "zsh"), proc(1, "bash"), proc(2, "node")];
+        rows[0].cpu = 50.0;
+        rows[1].cpu = 10.0;
+        rows[2].cpu = 1.0;
+        let names =
+            |rows: &[ProcessInfo]| -> Vec<String> { rows.iter().map(|p| p.name.clone()).collect() };
+        assert_eq!(
+            names(&selector_order(rows.clone(), None)),
+            ["zsh", "bash", "node"]
+        );
+        assert_eq!(
+            names(&selector_order(rows.clone(), Some(SortBy::Name))),
+            ["bash", "node", "zsh"]
+        );
+        let by_pid = selector_order(rows, Some(SortBy::Pid));
+        assert_eq!(by_pid.iter().map(|p| p.pid).collect::<Vec<_>>(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn hash_file_streams_sha256_of_a_temp_file() {
+        let path = temp_path("hash");
+        std::fs::write(&path, b"abc").unwrap();
+        let hash = hash_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            hash.as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+    }
+
+    #[test]
+    fn hash_file_of_an_empty_file() {
+        let path = temp_path("hash-empty");
+        std::fs::write(&path, b"").unwrap();
+        let hash = hash_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            hash.as_deref(),
+            Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+    }
+
+    #[test]
+    fn hash_matches_ignores_case_and_missing_files() {
+        let path = temp_path("hash-match");
+        std::fs::write(&path, b"abc").unwrap();
+        let hash = hash_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        let expected = "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD";
+        assert!(hash_matches(hash.as_deref(), expected));
+        assert!(!hash_matches(hash.as_deref(), &expected[1..]));
+        assert!(!hash_matches(
+            hash_file(&temp_path("missing")).as_deref(),
+            expected
+        ));
+    }
+
+    #[test]
+    fn set_config_value_adds_a_top_level_key_before_sections() {
+        let text = "danger_ports = [22]\n\n[signals]\nnode = \"INT\"\n";
+        let updated = set_config_value(text, "sort", "\"mem\"");
+        assert_eq!(
+            updated,
+            "danger_ports = [22]\n\nsort = \"mem\"\n[signals]\nnode = \"INT\"\n"
+        );
+        let config = parse_config(&updated).unwrap();
+        assert_eq!(config.sort, Some(SortBy::Mem));
+        assert_eq!(config.danger_ports, vec![22]);
+        assert_eq!(config.signal_rules.len(), 1);
+    }
+
+    #[test]
+    fn set_config_value_replaces_an_existing_key() {
+        let updated = set_config_value("sort = \"cpu\"\nsafe = true\n", "sort", "\"name\"");
+        assert_eq!(updated, "sort = \"name\"\nsafe = true\n");
+        let empty = set_config_value("", "sort", &format!("\"{}\"", sort_name(SortBy::Pid)));
+        assert_eq!(parse_config(&empty).unwrap().sort, Some(SortBy::Pid));
+    }
+
+    #[test]
+    fn next_sort_skips_port_outside_ports_mode() {
+        assert_eq!(next_sort(SortBy::Cpu, false), SortBy::Mem);
+        assert_eq!(next_sort(SortBy::Name, false), SortBy::Children);
+        assert_eq!(next_sort(SortBy::Name, true), SortBy::Port);
+        assert_eq!(next_sort(SortBy::FdPct, false), SortBy::Cpu);
+    }
+
+    #[test]
+    fn wrap_words_breaks_a_long_command_on_spaces() {
+        let cmd = "python3 -m http.server --bind 127.0.0.1 8000";
+        assert_eq!(
+            wrap_words(cmd, 16),
+            ["python3 -m", "http.server", "--bind 127.0.0.1", "8000"]
+        );
+    }
+
+    #[test]
+    fn wrap_words_splits_words_longer_than_the_width() {
+        assert_eq!(
+            wrap_words("--data-dir=/var/lib/app", 10),
+            ["--data-dir", "=/var/lib/", "app"]
+        );
+        assert!(wrap_words("", 10).is_empty());
+        assert_eq!(wrap_words("ab", 0), ["a", "b"]);
+    }
+
+    #[test]
+    fn parse_cmdline_splits_on_nul_and_handles_kernel_threads() {
+        assert_eq!(
+            parse_cmdline(b"node\0server.js\0--port\x003000\0"),
+            ["node", "server.js", "--port", "3000"]
+        );
+        assert!(parse_cmdline(b"").is_empty());
+    }
+
+    #[test]
+    fn sort_header_marks_only_the_active_column() {
+        assert_eq!(sort_header("CPU %", SortBy::Cpu, SortBy::Cpu), "CPU % ▼");
+        assert_eq!(sort_header("PID", SortBy::Pid, SortBy::Pid), "PID ▲");
+        assert_eq!(sort_header("NAME", SortBy::Name, SortBy::Name), "NAME ▲");
+        assert_eq!(sort_header("PID", SortBy::Pid, SortBy::Mem), "PID");
+    }
+
+    #[test]
+    fn numeric_sorts_run_descending() {
+        assert!(sort_descending(SortBy::Mem));
+        assert!(sort_descending(SortBy::Children));
+        assert!(!sort_descending(SortBy::Port));
+        assert!(!sort_descending(SortBy::Name));
+    }
+
+    #[test]
+    fn ssh_sessions_get_a_slower_default_interval() {
+        assert_eq!(default_interval_ms(false), 2000);
+        assert_eq!(default_interval_ms(true), SSH_INTERVAL_MS);
+        assert!(poll_interval(true) > poll_interval(false));
+    }
+
+    fn bound(port: u16, addr: IpAddr, ipv6: bool) -> PortBinding {
+        PortBinding {
+            port,
+            protocol: "TCP".to_string(),
+            addr,
+            ipv4: !ipv6,
+            ipv6,
+        }
+    }
+
+    #[test]
+    fn wildcard_binds_are_public_and_loopback_is_not() {
+        assert!(is_public_addr(IpAddr::from([0, 0, 0, 0])));
+        assert!(is_public_addr("::".parse().unwrap()));
+        assert!(is_public_addr(IpAddr::from([192, 168, 1, 5])));
+        assert!(!is_public_addr(IpAddr::from([127, 0, 0, 1])));
+        assert!(!is_public_addr("::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn add_binding_keeps_the_most_exposed_address() {
+        let mut map = PortMap::new();
+        add_binding(
+            &mut map,
+            7,
+            bound(8080, IpAddr::from([127, 0, 0, 1]), false),
+        );
+        add_binding(&mut map, 7, bound(8080, "::".parse().unwrap(), true));
+        add_binding(
+            &mut map,
+            7,
+            bound(9090, IpAddr::from([127, 0, 0, 1]), false),
+        );
+        let bindings = &map[&7];
+        assert_eq!(bindings.len(), 2);
+        assert_eq!(bindings[0].addr, "::".parse::<IpAddr>().unwrap());
+        assert_eq!(stack_label(&bindings[0]), "v4/v6");
+        assert_eq!(addr_label(Some(bindings[1].addr)), "127.0.0.1");
+        assert_eq!(addr_label(None), "-");
+    }
+
+    #[test]
+    fn public_only_filter_drops_loopback_listeners() {
+        let args = Args::try_parse_from(["rip", "--public-only"]).unwrap();
+        let listener = |pid, addr: IpAddr| ProcessInfo {
+            pid,
+            bind_addr: Some(addr),
+            ..Default::default()
+        };
+        let mut rows = vec![
+            listener(1, IpAddr::from([0, 0, 0, 0])),
+            listener(2, IpAddr::from([127, 0, 0, 1])),
+        ];
+        apply_filters(&mut rows, &args, Path::new("/proc"));
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn pick_by_age_selects_by_run_time() {
+        let aged = |pid, run_time| ProcessInfo {
+            pid,
+            run_time,
+            ..Default::default()
+        };
+        let rows = vec![aged(30, 120), aged(10, 5000), aged(20, 5000), aged(40, 3)];
+        assert_eq!(pick_by_age(&rows, true).map(|p| p.pid), Some(10));
+        assert_eq!(pick_by_age(&rows, false).map(|p| p.pid), Some(40));
+        assert!(pick_by_age(&[], true).is_none());
+    }
+
+    #[test]
+    fn format_run_time_uses_the_two_largest_units() {
+        assert_eq!(format_run_time(42), "42s");
+        assert_eq!(format_run_time(125), "2m 5s");
+        assert_eq!(format_run_time(7260), "2h 1m");
+        assert_eq!(format_run_time(90000), "1d 1h");
+    }
+
+    #[test]
+    fn memory_growth_measures_from_first_observation() {
+        let sized = |pid, mb: u64| ProcessInfo {
+            pid,
+            memory: mb * MB,
+            ..Default::default()
+        };
+        let mut baselines = HashMap::new();
+        let mut rows = vec![sized(1, 100), sized(2, 50)];
+        memory_growth(&mut baselines, &mut rows);
+        assert_eq!(rows[0].mem_growth, 0);
+
+        let mut rows = vec![sized(1, 220), sized(2, 40)];
+        memory_growth(&mut baselines, &mut rows);
+        assert_eq!(rows[0].mem_growth, 120);
+        assert_eq!(rows[1].mem_growth, -10);
+        assert!(exceeds_mem_alert(rows[0].mem_growth, 100));
+        assert!(!exceeds_mem_alert(rows[1].mem_growth, 0));
+        assert_eq!(format_growth(120), "+120 MB");
+        assert_eq!(format_growth(-10), "-10 MB");
+
+        // A PID that disappears and comes back starts a fresh baseline.
+        let mut rows = vec![sized(1, 220)];
+        memory_growth(&mut baselines, &mut rows);
+        let mut rows = vec![sized(2, 300)];
+        memory_growth(&mut baselines, &mut rows);
+        assert_eq!(rows[0].mem_growth, 0);
+    }
+
+    #[test]
+    fn join_entries_uses_nul_separators_with_print0() {
+        let entries = vec!["12".to_string(), "my app\n".to_string()];
+        assert_eq!(join_entries(&entries, true), "12\0my app\n\0");
+        assert_eq!(join_entries(&entries, false), "12\nmy app\n\n");
+        assert_eq!(join_entries(&[], true), "");
+    }
+
+    #[test]
+    fn write_entries_writes_the_file_with_nul_separators() {
+        let path = temp_path("print0");
+        let entries = vec!["1".to_string(), "2".to_string()];
+        write_entries(Some(&path), &entries, true).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(written, b"1\x002\x00");
+    }
+
+    #[test]
+    fn top_shortcuts_resolve_to_sort_and_limit() {
+        assert_eq!(top_shortcut(Some(5), None), Some((SortBy::Cpu, 5)));
+        assert_eq!(top_shortcut(None, Some(3)), Some((SortBy::Mem, 3)));
+        assert_eq!(top_shortcut(None, None), None);
+        assert!(Args::try_parse_from(["rip", "--top-cpu", "5", "--top-mem", "3"]).is_err());
+        assert!(Args::try_parse_from(["rip", "--top-cpu", "5", "--sort", "mem"]).is_err());
+        assert!(Args::try_parse_from(["rip", "--top-mem", "5", "--sort", "cpu"]).is_err());
+    }
+
+    #[test]
+    fn windowed_cpu_divides_ticks_by_wall_time() {
+        assert_eq!(windowed_cpu(50, 100.0, Duration::from_secs(1)), 50.0);
+        assert_eq!(windowed_cpu(400, 100.0, Duration::from_secs(2)), 200.0);
+        assert_eq!(windowed_cpu(10, 100.0, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn cpu_window_measures_against_the_oldest_sample_in_the_window() {
+        let mut window = CpuWindow {
+            window: Duration::from_secs(2),
+            ticks_per_sec: 100.0,
+            history: HashMap::new(),
+        };
+        let start = Instant::now();
+        let ticking = |ticks| ProcessInfo {
+            pid: 7,
+            cpu_ticks: Some(ticks),
+            ..Default::default()
+        };
+        let mut rows = vec![ticking(0)];
+        window.update(&mut rows, start);
+        assert_eq!(rows[0].cpu, 0.0);
+        let mut rows = vec![ticking(100)];
+        window.update(&mut rows, start + Duration::from_secs(1));
+        assert_eq!(rows[0].cpu, 100.0);
+        let mut rows = vec![ticking(150)];
+        window.update(&mut rows, start + Duration::from_secs(3));
+        // The t=0 sample is outside the window now, so t=1 is the base.
+        assert_eq!(rows[0].cpu, 25.0);
+    }
+
+    #[test]
+    fn exec_template_substitutes_each_pid() {
+        assert_eq!(
+            build_exec_commands("renice 10 -p {}", &[4, 5]),
+            vec![
+                vec!["renice", "10", "-p", "4"],
+                vec!["renice", "10", "-p", "5"],
+            ]
+        );
+        assert_eq!(
+            build_exec_commands("gdb --pid={}", &[9]),
+            vec![vec!["gdb", "--pid=9"]]
+        );
+    }
+
+    #[test]
+    fn exec_template_without_placeholder_appends_all_pids() {
+        assert_eq!(
+            build_exec_commands("kill -STOP", &[4, 5]),
+            vec![vec!["kill", "-STOP", "4", "5"]]
+        );
+        assert!(build_exec_commands("kill", &[]).is_empty());
+        assert!(build_exec_commands("  ", &[4]).is_empty());
+    }
+
+    #[test]
+    fn zombies_redirect_to_their_parent() {
+        let proc = |zombie, parent| ProcessInfo {
+            pid: 50,
+            zombie,
+            parent,
+            ..Default::default()
+        };
+        assert_eq!(zombie_action(&proc(false, Some(10))), ZombieAction::Signal);
+        assert_eq!(
+            zombie_action(&proc(true, Some(10))),
+            ZombieAction::ReapParent(10)
+        );
+        // init reaps on its own; signalling it is never the answer.
+        assert_eq!(zombie_action(&proc(true, Some(1))), ZombieAction::Skip);
+        assert_eq!(zombie_action(&proc(true, None)), ZombieAction::Skip);
+    }
+
+    #[test]
+    fn queries_combine_with_and_or_not() {
+        let proc = ProcessInfo {
+            pid: 300,
+            name: "Node".to_string(),
+            cpu: 45.0,
+            memory: 600 * MB,
+            port: Some(3000),
+            owner: Some("alice".to_string()),
+            ..Default::default()
+        };
+        let matches = |q: &str| query_matches(&parse_query(q).unwrap(), &proc);
+        assert!(matches("cpu > 40 and mem >= 600"));
+        assert!(matches("name = node or pid < 10"));
+        assert!(matches("not (port == 8080) and user ~ ali"));
+        assert!(matches("NAME ~ 'od'"));
+        assert!(!matches("cpu > 40 and not name ~ no"));
+        assert!(!matches("port != 3000"));
+    }
+
+    #[test]
+    fn mem_queries_keep_fractional_megabytes() {
+        let proc = ProcessInfo { memory: 768 * 1024, ..Default::default() };
+        let matches = |q: &str| query_matches(&parse_query(q).unwrap(), &proc);
+        assert!(matches("mem > 0.5"));
+        assert!(!matches("mem >= 1"));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let query = parse_query("pid = 1 or pid = 2 and cpu > 5").unwrap();
+        assert!(matches!(query, Query::Or(_, _)));
+    }
+
+    #[test]
+    fn malformed_queries_name_the_offending_token() {
+        let err = |q: &str| parse_query(q).unwrap_err();
+        assert_eq!(
+            err("cpu > high"),
+            "unexpected token 'high', expected a number"
+        );
+        assert_eq!(err("colour = red"), "unknown field 'colour'");
+        assert_eq!(err("cpu > 5 pid"), "unexpected token 'pid'");
+        assert_eq!(
+            err("cpu ~ 5"),
+            "unexpected token '~', it only applies to name and user"
+        );
+        assert_eq!(err("(cpu > 5"), "unexpected end of expression");
+        assert_eq!(
+            err("name = 'x"),
+            "unterminated string starting at position 7"
+        );
+    }
+
+    #[test]
+    fn no_alt_screen_renders_inline_above_the_prompt() {
+        assert!(Args::try_parse_from(["rip", "--no-alt-screen"]).is_err());
+        let args = Args::try_parse_from(["rip", "--live", "--no-alt-screen"]).unwrap();
+        assert!(args.no_alt_screen);
+        assert_eq!(inline_height(40), 39);
+        assert_eq!(inline_height(3), 5);
+        assert_eq!(inline_height(0), 5);
+    }
+
+    #[test]
+    fn args_sort_puts_the_longest_command_lines_first() {
+        let with_args = |pid, n: usize| ProcessInfo {
+            pid,
+            cmd: vec!["arg".to_string(); n],
+            ..Default::default()
+        };
+        let mut rows = vec![with_args(1, 2), with_args(2, 0), with_args(3, 5000)];
+        sort_processes(&mut rows, SortBy::Args);
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [3, 1, 2]);
+    }
+
+    #[test]
+    fn watch_records_samples_until_the_process_exits() {
+        // No such PID, so the /proc reads come back empty.
+        let proc = ProcessInfo {
+            pid: u32::MAX,
+            name: "worker".to_string(),
+            cpu: 12.4,
+            memory: 64 * MB,
+            ..Default::default()
+        };
+        let mut watch = Watch::new(&proc, Settings::default().proc_root);
+        assert!(!watch.exited);
+        assert_eq!(watch.cpu, [12]);
+        assert_eq!(watch.memory, [64 * MB]);
+        assert_eq!(optional_count(watch.fds), "-");
+
+        watch.update(Some(&proc));
+        assert_eq!(watch.cpu.len(), 2);
+
+        watch.update(None);
+        assert!(watch.exited);
+        // A later row with the same PID is a new process, not the watched one.
+        watch.update(Some(&proc));
+        assert!(watch.exited);
+        assert_eq!(watch.cpu.len(), 2);
+        assert_eq!(optional_count(Some(3)), "3");
+    }
+
+    #[test]
+    fn signal_rules_match_by_name_and_fall_back_to_the_cli_signal() {
+        let config = parse_config("[signals]\nNode = \"INT\"\npostgres = \"QUIT\"\n").unwrap();
+        assert_eq!(config.signal_rules[0].0, "node");
+        let named = |name: &str| ProcessInfo {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let rules = &config.signal_rules;
+        assert_eq!(
+            signal_for(&named("node-server"), rules, Signal::SIGTERM),
+            Signal::SIGINT
+        );
+        assert_eq!(
+            signal_for(&named("Postgres"), rules, Signal::SIGKILL),
+            Signal::SIGQUIT
+        );
+        assert_eq!(
+            signal_for(&named("nginx"), rules, Signal::SIGKILL),
+            Signal::SIGKILL
+        );
+        // The first matching rule wins.
+        let overlapping = vec![
+            ("node".to_string(), Signal::SIGINT),
+            ("no".to_string(), Signal::SIGHUP),
+        ];
+        assert_eq!(
+            signal_for(&named("node"), &overlapping, Signal::SIGTERM),
+            Signal::SIGINT
+        );
+        assert_eq!(signal_label(Signal::SIGINT), "SIGINT");
+    }
+
+    #[test]
+    fn bucket_counts_places_values_below_each_bound() {
+        let cpu = [0.0, 9.9, 10.0, 49.0, 50.0, 300.0];
+        assert_eq!(bucket_counts(cpu.into_iter(), &CPU_BUCKETS), [2, 2, 2]);
+        let mem = [5, 99, 100, 700, 1024, 4096];
+        assert_eq!(bucket_counts(mem.into_iter(), &MEM_BUCKETS), [2, 1, 1, 2]);
+        assert_eq!(
+            bucket_counts(std::iter::empty::<u64>(), &MEM_BUCKETS),
+            [0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn watch_port_done_tracks_the_requested_condition() {
+        let mut map = PortMap::new();
+        map.insert(9, vec![bound(3000, IpAddr::from([127, 0, 0, 1]), false)]);
+        assert!(!watch_port_done(&map, 3000, true));
+        assert!(watch_port_done(&map, 3000, false));
+        assert!(watch_port_done(&map, 4000, true));
+        assert!(!watch_port_done(&map, 4000, false));
+        assert!(watch_port_done(&PortMap::new(), 3000, true));
+    }
+
+    #[test]
+    fn spawn_order_sorts_siblings_by_start_time_with_indices() {
+        let node = |pid, parent, start_time| ProcessInfo {
+            pid,
+            parent,
+            start_time,
+            ..Default::default()
+        };
+        let rows = vec![
+            node(10, None, 100),
+            node(13, Some(10), 300),
+            node(11, Some(10), 200),
+            node(12, Some(10), 200),
+        ];
+        let ordered = tree_order(rows, true);
+        let got: Vec<(u32, &str)> = ordered
+            .iter()
+            .map(|p| (p.pid, p.tree_prefix.as_str()))
+            .collect();
+        assert_eq!(
+            got,
+            [(10, ""), (11, "├─ 1. "), (12, "├─ 2. "), (13, "└─ 3. ")]
+        );
+    }
+
+    #[test]
+    fn tree_order_keeps_the_current_sort_without_spawn_order() {
+        let node = |pid, parent, start_time| ProcessInfo {
+            pid,
+            parent,
+            start_time,
+            ..Default::default()
+        };
+        let rows = vec![
+            node(10, None, 100),
+            node(13, Some(10), 300),
+            node(11, Some(10), 200),
+        ];
+        let pids: Vec<u32> = tree_order(rows, false).iter().map(|p| p.pid).collect();
+        assert_eq!(pids, [10, 13, 11]);
+    }
+
+    #[test]
+    fn missing_tty_only_gates_modes_that_prompt() {
+        let parse = |argv: &[&str]| Args::try_parse_from(argv).unwrap();
+        let piped = Terminals {
+            stdin: false,
+            stdout: true,
+            stderr: true,
+        };
+        let tty = Terminals {
+            stdin: true,
+            stdout: true,
+            stderr: true,
+        };
+        assert_eq!(
+            missing_tty(&parse(&["rip"]), false, piped),
+            Some("the interactive selector")
+        );
+        assert_eq!(missing_tty(&parse(&["rip"]), false, tty), None);
+        assert_eq!(
+            missing_tty(&parse(&["rip", "--live"]), false, piped),
+            Some("live mode")
+        );
+        assert_eq!(missing_tty(&parse(&["rip", "--names"]), false, piped), None);
+        assert_eq!(
+            missing_tty(
+                &parse(&["rip", "-f", "node", "--confirm-nuke"]),
+                false,
+                piped
+            ),
+            None
+        );
+        assert!(missing_tty(&parse(&["rip", "--pid", "42", "--confirm"]), false, piped).is_some());
+    }
+
+    #[test]
+    fn live_title_names_the_active_signal() {
+        assert_eq!(
+            live_title(0, 0, Signal::SIGTERM, false, None),
+            " rip - signal: TERM "
+        );
+        assert_eq!(
+            live_title(2, 1, Signal::SIGKILL, true, Some("sorted by mem")),
+            " rip - 2 selected (1 hidden) - signal: KILL (+rules) - sorted by mem "
+        );
+    }
+
+    #[test]
+    fn smart_filter_only_treats_all_digit_filters_as_pids() {
+        let parse = |argv: &[&str]| Args::try_parse_from(argv).unwrap();
+        assert_eq!(
+            smart_pid_filter(&parse(&["rip", "-f", "8080", "--smart-filter"])),
+            Some("8080")
+        );
+        assert_eq!(smart_pid_filter(&parse(&["rip", "-f", "8080"])), None);
+        assert_eq!(
+            smart_pid_filter(&parse(&["rip", "-f", "node2", "--smart-filter"])),
+            None
+        );
+        assert!(Args::try_parse_from(["rip", "--smart-filter"]).is_err());
+    }
+
+    #[test]
+    fn numeric_filter_falls_back_to_pid_substrings() {
+        assert!(pid_matches(4312, "31"));
+        assert!(pid_matches(4312, "4312"));
+        assert!(!pid_matches(4312, "43120"));
+        assert!(!pid_matches(7, "3"));
+    }
+
+    #[test]
+    fn push_capped_drops_the_oldest_sample_once_full() {
+        let mut buffer = VecDeque::new();
+        push_capped(&mut buffer, 1, 3);
+        assert_eq!(buffer, [1]);
+        push_capped(&mut buffer, 2, 3);
+        push_capped(&mut buffer, 3, 3);
+        push_capped(&mut buffer, 4, 3);
+        assert_eq!(buffer, [2, 3, 4]);
+    }
+
+    #[test]
+    fn tty_nr_decodes_to_a_device_name() {
+        let stat =
+            parse_proc_stat("300 (vim) S 200 300 200 34817 300 0 0 0 0 0 1 1 0 0 20 0").unwrap();
+        assert_eq!(stat.tty_nr, 34817);
+        assert_eq!(tty_name(stat.tty_nr).as_deref(), Some("pts/1"));
+        assert_eq!(tty_name(0x0401).as_deref(), Some("tty1"));
+        assert_eq!(tty_name(0x0440).as_deref(), Some("ttyS0"));
+        assert_eq!(tty_name(0), None);
+    }
+
+    #[test]
+    fn tty_filters_split_terminal_and_background_processes() {
+        let with_tty = |pid, tty: Option<&str>| ProcessInfo {
+            pid,
+            tty: tty.map(str::to_string),
+            ..Default::default()
+        };
+        let rows = vec![with_tty(1, Some("pts/0")), with_tty(2, None)];
+        let pids = |argv: &[&str]| {
+            let mut rows = rows.clone();
+            let args = Args::try_parse_from(argv).unwrap();
+            apply_filters(&mut rows, &args, Path::new("/proc"));
+            rows.iter().map(|p| p.pid).collect::<Vec<_>>()
+        };
+        assert_eq!(pids(&["rip", "--only-with-tty"]), [1]);
+        assert_eq!(pids(&["rip", "--no-tty"]), [2]);
+    }
+
+    #[test]
+    fn safe_mode_requires_a_filter_before_killing() {
+        let parse = |argv: &[&str]| Args::try_parse_from(argv).unwrap();
+        assert!(safe_mode_error(&parse(&["rip"]), true).is_some());
+        assert!(safe_mode_error(&parse(&["rip"]), false).is_none());
+        assert!(safe_mode_error(&parse(&["rip", "-f", "node"]), true).is_none());
+        assert!(safe_mode_error(&parse(&["rip", "--pid", "42"]), true).is_none());
+        assert!(safe_mode_error(&parse(&["rip", "--names"]), true).is_none());
+    }
+
+    #[test]
+    fn safe_mode_needs_a_terminal_to_confirm() {
+        let no_tty = Terminals {
+            stdin: false,
+            stdout: false,
+            stderr: false,
+        };
+        let args = Args::try_parse_from(["rip", "--pid", "42"]).unwrap();
+        assert_eq!(missing_tty(&args, false, no_tty), None);
+        assert_eq!(
+            missing_tty(&args, true, no_tty),
+            Some("the interactive selector")
+        );
+        let args = Args::try_parse_from(["rip", "-f", "node", "--confirm-nuke"]).unwrap();
+        assert_eq!(missing_tty(&args, false, no_tty), None);
+        assert!(missing_tty(&args, true, no_tty).is_some());
+    }
+
+    #[test]
+    fn parse_proc_stat_reads_the_start_time() {
+        let line =
+            "42 (my (odd) name) S 1 42 42 34816 42 4194304 0 0 0 0 7 3 0 0 20 5 1 0 123456 0 0";
+        let stat = parse_proc_stat(line).unwrap();
+        assert_eq!(stat.comm, "my (odd) name");
+        assert_eq!((stat.utime, stat.stime), (7, 3));
+        assert_eq!(stat.nice, Some(5));
+        assert_eq!(stat.start_ticks, Some(123456));
+    }
+
+    #[test]
+    fn same_process_compares_start_times() {
+        assert!(same_process(Some(10), Some(10)));
+        assert!(!same_process(Some(10), Some(11)));
+        assert!(!same_process(Some(10), None));
+        assert!(same_process(None, Some(11)));
+    }
+
+    #[test]
+    fn signal_path_prefers_pidfd_and_skips_stale_rows() {
+        let mut proc = ProcessInfo::default();
+        assert_eq!(signal_path(&proc), SignalPath::Kill);
+        proc.stale = true;
+        assert_eq!(signal_path(&proc), SignalPath::Skip);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn attach_pidfds_verifies_the_start_time() {
+        let own = std::process::id();
+        let start_ticks = read_proc_stat(Path::new("/proc"), own).and_then(|stat| stat.start_ticks);
+        let mut rows = vec![
+            ProcessInfo {
+                pid: own,
+                start_ticks,
+                ..Default::default()
+            },
+            ProcessInfo {
+                pid: own,
+                start_ticks: start_ticks.map(|t| t + 1),
+                ..Default::default()
+            },
+        ];
+        attach_pidfds(&mut rows, Path::new("/proc"));
+        if rows[0].pidfd.is_some() {
+            assert_eq!(signal_path(&rows[0]), SignalPath::Pidfd);
+        } else {
+            assert_eq!(signal_path(&rows[0]), SignalPath::Kill);
+        }
+        assert!(!rows[0].stale);
+        if open_pidfd(own).is_ok() {
+            assert!(rows[1].stale);
+            assert_eq!(signal_path(&rows[1]), SignalPath::Skip);
+        }
+    }
+
+    #[test]
+    fn minimal_selector_omits_the_header_and_help() {
+        let first = ProcessInfo {
+            name_width: 10,
+            ..Default::default()
+        };
+        let (header, help) = selector_chrome(&first, false, true);
+        assert!(header.is_empty());
+        assert_eq!(help, None);
+        let (header, help) = selector_chrome(&first, true, false);
+        assert!(header.contains("PID") && header.contains("NAME"));
+        assert!(header.contains(&port_header(first.columns)));
+        assert_eq!(help, Some(SELECTOR_HELP));
+    }
+
+    #[test]
+    fn v4_and_v6_listeners_on_one_port_are_dual_stack() {
+        let mut map = PortMap::new();
+        add_binding(&mut map, 3, bound(5432, IpAddr::from([0, 0, 0, 0]), false));
+        add_binding(&mut map, 3, bound(5432, "::".parse().unwrap(), true));
+        add_binding(&mut map, 3, bound(6379, "::1".parse().unwrap(), true));
+        add_binding(
+            &mut map,
+            3,
+            bound(8000, IpAddr::from([127, 0, 0, 1]), false),
+        );
+        let labels: Vec<(u16, &str)> = map[&3].iter().map(|b| (b.port, stack_label(b))).collect();
+        assert_eq!(labels, [(5432, "v4/v6"), (6379, "v6"), (8000, "v4")]);
+    }
+
+    #[test]
+    fn write_snapshot_saves_a_tsv_with_a_header() {
+        let dir = temp_path("snapshot-dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        let proc = ProcessInfo {
+            pid: 42,
+            name: "tab\tname".to_string(),
+            cpu: 3.25,
+            memory: 10 * MB,
+            cmd: vec!["srv".to_string(), "--flag\n".to_string()],
+            ..Default::default()
+        };
+        let path = write_snapshot(&dir, &[proc], 1700000000).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(path.ends_with("rip-snapshot-1700000000.tsv"));
+        let mut lines = written.lines();
+        assert_eq!(lines.next(), Some(SNAPSHOT_COLUMNS.join("\t").as_str()));
+        assert_eq!(
+            lines.next(),
+            Some("42\ttab name\t3.2\t10\t\t\t\t0\tsrv --flag ")
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn write_snapshot_reports_an_unwritable_directory() {
+        let missing = temp_path("snapshot-missing").join("nope");
+        assert!(write_snapshot(&missing, &[], 1).is_err());
+    }
+
+    #[test]
+    fn memory_column_follows_the_chosen_metric() {
+        let parse = |argv: &[&str]| Args::try_parse_from(argv).unwrap().mem_kind;
+        assert_eq!(parse(&["rip"]), MemKind::Rss);
+        assert_eq!(parse(&["rip", "--mem-kind", "virtual"]), MemKind::Virtual);
+        assert_eq!(
+            (MemKind::Rss.label(), MemKind::Rss.status_field()),
+            ("RSS", "VmRSS")
+        );
+        assert_eq!(
+            (MemKind::Virtual.label(), MemKind::Virtual.status_field()),
+            ("VIRT", "VmSize")
+        );
+    }
+
+    #[test]
+    fn visible_rows_skip_descendants_of_collapsed_pids() {
+        let node = |pid, parent| ProcessInfo {
+            pid,
+            parent,
+            ..Default::default()
+        };
+        let rows = tree_order(
+            vec![
+                node(1, None),
+                node(2, Some(1)),
+                node(3, Some(2)),
+                node(4, Some(3)),
+                node(5, Some(1)),
+            ],
+            false,
+        );
+        let pids = |collapsed: &[u32]| -> Vec<u32> {
+            let collapsed: HashSet<u32> = collapsed.iter().copied().collect();
+            visible_rows(&rows, &collapsed)
+                .iter()
+                .map(|&i| rows[i].pid)
+                .collect()
+        };
+        assert_eq!(pids(&[]), [1, 2, 3, 4, 5]);
+        assert_eq!(pids(&[2]), [1, 2, 5]);
+        assert_eq!(pids(&[1]), [1]);
+        assert_eq!(pids(&[4]), [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn repeat_stops_when_nothing_remains_or_rounds_run_out() {
+        assert!(repeat_continues(1, 10, 3));
+        assert!(!repeat_continues(1, 10, 0));
+        assert!(!repeat_continues(10, 10, 3));
+        assert!(repeat_continues(500, 0, 1));
+    }
+
+    #[test]
+    fn repeat_targets_skip_zombies_self_and_shell() {
+        let rows = vec![
+            ProcessInfo {
+                pid: 1,
+                ..Default::default()
+            },
+            ProcessInfo {
+                pid: 2,
+                zombie: true,
+                ..Default::default()
+            },
+            ProcessInfo {
+                pid: 3,
+                is_self: true,
+                ..Default::default()
+            },
+            ProcessInfo {
+                pid: 4,
+                is_shell: true,
+                ..Default::default()
+            },
+        ];
+        let pids: Vec<u32> = repeat_targets(rows).iter().map(|p| p.pid).collect();
+        assert_eq!(pids, vec![1]);
+        let zombies = vec![ProcessInfo {
+            pid: 2,
+            zombie: true,
+            ..Default::default()
+        }];
+        assert!(!repeat_continues(1, 10, repeat_targets(zombies).len()));
+    }
+
+    #[test]
+    fn repeat_needs_a_filter_that_survives_a_restart() {
+        let parse = |argv: &[&str]| Args::try_parse_from(argv);
+        assert!(parse(&["rip", "--repeat", "-f", "worker"]).is_err());
+        assert!(parse(&["rip", "--repeat", "--confirm-nuke", "--pid", "42"]).is_err());
+        let args = parse(&["rip", "--repeat", "--confirm-nuke", "--query", "cpu > 50"]).unwrap();
+        assert!(validate_args(&args).is_ok());
+        let args = parse(&["rip", "--repeat", "--confirm-nuke", "--session", "7"]).unwrap();
+        assert!(validate_args(&args).is_err());
+    }
+
+    #[test]
+    fn port_colour_distinguishes_udp_and_danger_ports() {
+        let theme = Theme::named(ThemeName::Dark);
+        let port = |protocol: &str, danger| ProcessInfo {
+            port: Some(53),
+            protocol: Some(protocol.to_string()),
+            danger,
+            ..Default::default()
+        };
+        assert_eq!(theme.port(&port("TCP", false)), theme.good);
+        assert_eq!(theme.port(&port("udp", false)), theme.udp);
+        assert_eq!(theme.port(&port("UDP", true)), theme.hot);
+        assert_ne!(theme.udp, theme.good);
+        assert!(is_udp(Some("Udp")));
+        assert!(!is_udp(None));
+    }
+
+    #[test]
+    fn idle_processes_tie_break_on_memory_then_pid() {
+        let idle = |pid, mb: u64| ProcessInfo {
+            pid,
+            memory: mb * MB,
+            ..Default::default()
+        };
+        let busy = ProcessInfo {
+            pid: 99,
+            cpu: 2.0,
+            ..Default::default()
+        };
+        let mut rows = vec![idle(5, 10), idle(3, 10), busy, idle(8, 300), idle(1, 0)];
+        sort_processes(&mut rows, SortBy::Cpu);
+        assert_eq!(
+            rows.iter().map(|p| p.pid).collect::<Vec<_>>(),
+            [99, 8, 3, 5, 1]
+        );
+    }
+
+    #[test]
+    fn single_target_overrides_the_marked_selection() {
+        let marked: HashSet<u32> = [4, 5].into();
+        assert_eq!(kill_set(&marked, Some(9)), HashSet::from([9]));
+        assert_eq!(kill_set(&marked, None), marked);
+        assert!(kill_set(&HashSet::new(), None).is_empty());
+    }
+
+    #[test]
+    fn cap_selector_truncates_only_above_the_cap() {
+        let rows = |n: u32| -> Vec<ProcessInfo> {
+            (0..n)
+                .map(|pid| ProcessInfo {
+                    pid,
+                    ..Default::default()
+                })
+                .collect()
+        };
+        let (kept, total) = cap_selector(rows(3), 3);
+        assert_eq!((kept.len(), total), (3, None));
+        let (kept, total) = cap_selector(rows(5000), SELECTOR_CAP);
+        assert_eq!((kept.len(), total), (SELECTOR_CAP, Some(5000)));
+        assert_eq!(kept.last().map(|p| p.pid), Some(SELECTOR_CAP as u32 - 1));
+    }
+
+    #[test]
+    fn thread_cpu_folds_into_the_owning_process() {
+        let row = |pid, cpu, thread_of| ProcessInfo {
+            pid,
+            cpu,
+            thread_of,
+            ..Default::default()
+        };
+        let rows = vec![
+            row(10, 5.0, None),
+            row(11, 30.0, Some(10)),
+            row(12, 25.0, Some(10)),
+            row(20, 80.0, None),
+            row(21, 10.0, Some(20)),
+            row(31, 4.0, Some(30)),
+        ];
+        let merged: Vec<(u32, f32)> = merge_thread_cpu(rows)
+            .iter()
+            .map(|p| (p.pid, p.cpu))
+            .collect();
+        // 20 already counts its thread; 31's owner isn't listed, so it stays.
+        assert_eq!(merged, [(10, 55.0), (20, 80.0), (31, 4.0)]);
+    }
+
+    #[test]
+    fn tsv_header_row_matches_the_data_columns() {
+        let proc = ProcessInfo {
+            pid: 7,
+            name: "db".to_string(),
+            port: Some(5432),
+            protocol: Some("TCP".to_string()),
+            ..Default::default()
+        };
+        let with_header = snapshot_tsv(std::slice::from_ref(&proc), Some("#"));
+        let mut lines = with_header.lines();
+        let header = lines.next().unwrap();
+        assert_eq!(
+            header,
+            "#pid\tname\tcpu\tmemory_mb\tport\tprotocol\towner\tstart_time\tcommand"
+        );
+        let row = lines.next().unwrap();
+        assert_eq!(row.split('\t').count(), header.split('\t').count());
+        assert_eq!(snapshot_tsv(&[proc], None), format!("{}\n", row));
+    }
+
+    #[test]
+    fn ports_without_a_known_process_are_listed_as_unknown() {
+        let mut map = PortMap::new();
+        map.insert(10, vec![bound(80, IpAddr::from([0, 0, 0, 0]), false)]);
+        map.insert(404, vec![bound(9000, IpAddr::from([0, 0, 0, 0]), false)]);
+        let known: HashSet<u32> = [10].into();
+        let rows = orphan_port_rows(&map, &known, None, None);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            (rows[0].pid, rows[0].name.as_str(), rows[0].port),
+            (404, "<unknown>", Some(9000))
+        );
+        assert!(orphan_port_rows(&map, &[10, 404].into(), None, None).is_empty());
+        assert!(orphan_port_rows(&map, &known, Some("node"), None).is_empty());
+    }
+
+    #[test]
+    fn shuffle_keeps_every_pid_but_changes_the_order() {
+        let mut rows: Vec<ProcessInfo> = (1..=50)
+            .map(|pid| ProcessInfo {
+                pid,
+                ..Default::default()
+            })
+            .collect();
+        shuffle_processes(&mut rows, 12345);
+        let shuffled: Vec<u32> = rows.iter().map(|p| p.pid).collect();
+        assert_ne!(shuffled, (1..=50).collect::<Vec<_>>());
+        let mut sorted = shuffled.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (1..=50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn column_gap_pads_the_separator() {
+        assert_eq!(column_gap(" ", 0), " ");
+        assert_eq!(column_gap("|", 1), " | ");
+        assert_eq!(column_gap("\t", 0), "\t");
+        let args = Args::try_parse_from(["rip", "--separator", ",", "--pad", "2"]).unwrap();
+        assert_eq!(column_gap(&args.separator, args.pad), "  ,  ");
+    }
+
+    #[test]
+    fn ancestor_chain_stops_at_the_session_leader() {
+        let parents: HashMap<u32, u32> = [(500, 400), (400, 300), (300, 200), (200, 1)].into();
+        let parent_of = |pid| parents.get(&pid).copied();
+        assert_eq!(ancestor_chain(parent_of, 500, Some(300)), [400, 300]);
+        assert_eq!(ancestor_chain(parent_of, 500, None), [400, 300, 200]);
+        assert!(ancestor_chain(parent_of, 200, None).is_empty());
+    }
+
+    #[test]
+    fn ancestor_chain_survives_a_parent_loop() {
+        let parents: HashMap<u32, u32> = [(10, 20), (20, 30), (30, 20)].into();
+        assert_eq!(
+            ancestor_chain(|pid| parents.get(&pid).copied(), 10, None),
+            [20, 30]
+        );
+    }
+
+    #[test]
+    fn mark_shell_flags_only_the_ancestors() {
+        let mut rows: Vec<ProcessInfo> = [300, 301]
+            .into_iter()
+            .map(|pid| ProcessInfo {
+                pid,
+                ..Default::default()
+            })
+            .collect();
+        mark_shell(&mut rows, &[300].into());
+        assert!(rows[0].is_shell && !rows[1].is_shell);
+        assert!(guard_self(rows).iter().all(|p| p.pid == 301));
+    }
+
+    #[test]
+    fn selection_summary_totals_memory_and_cpu() {
+        let proc = |cpu, mb: u64| ProcessInfo {
+            cpu,
+            memory: mb * MB,
+            ..Default::default()
+        };
+        assert_eq!(
+            selection_summary(&[proc(12.5, 512), proc(3.0, 1024)]),
+            "2 processes matched, total 1.5 GB, 15.5% CPU"
+        );
+        assert_eq!(
+            selection_summary(&[proc(0.0, 2)]),
+            "1 process matched, total 2.0 MB, 0.0% CPU"
+        );
+    }
 
-    disable_raw_mode()?;
-    stdout().execute(LeaveAlternateScreen)?;
+    #[test]
+    fn profile_flags_go_before_the_command_line() {
+        let config =
+            parse_config("[profiles.dev]\nsort = \"mem\"\nlimit = 5\ntree = true\nsafe = false\n")
+                .unwrap();
+        let argv = ["rip", "--limit", "2"]
+            .map(std::ffi::OsString::from)
+            .to_vec();
+        let merged = profile_argv(&config, "dev", argv).unwrap();
+        assert_eq!(
+            merged,
+            ["rip", "--sort", "mem", "--limit", "5", "--tree", "--limit", "2"]
+        );
+        // main() parses the merged argv the same way.
+        let matches = Args::command()
+            .args_override_self(true)
+            .try_get_matches_from(merged)
+            .unwrap();
+        let args = Args::from_arg_matches(&matches).unwrap();
+        assert_eq!(
+            (args.sort, args.limit, args.tree),
+            (SortBy::Mem, Some(2), true)
+        );
+        assert_eq!(
+            profile_argv(&config, "prod", Vec::new()).unwrap_err(),
+            "Error: unknown profile prod"
+        );
+    }
 
-    if !selected_pids.is_empty() {
-        let to_kill: Vec<ProcessInfo> = processes
-            .into_iter()
-            .filter(|p| selected_pids.contains(&p.pid))
+    #[test]
+    fn profile_arrays_repeat_the_flag() {
+        let entries = vec![(
+            "danger_port".to_string(),
+            ConfigValue::Array(vec![ConfigValue::Int(22), ConfigValue::Int(5432)]),
+        )];
+        assert_eq!(
+            profile_args(&entries),
+            ["--danger-port", "22", "--danger-port", "5432"]
+        );
+    }
+
+    #[test]
+    fn dedupe_by_name_keeps_the_busiest_instance_in_place() {
+        let proc = |pid, name: &str, cpu| ProcessInfo {
+            pid,
+            name: name.to_string(),
+            cpu,
+            ..Default::default()
+        };
+        let rows = vec![
+            proc(1, "chrome", 5.0),
+            proc(2, "bash", 1.0),
+            proc(3, "chrome", 40.0),
+            proc(4, "chrome", 2.0),
+        ];
+        let deduped: Vec<(u32, usize)> = dedupe_by_name(rows)
+            .iter()
+            .map(|p| (p.pid, p.duplicates))
             .collect();
-        kill_processes(to_kill, signal);
+        assert_eq!(deduped, [(3, 3), (2, 1)]);
     }
 
-    Ok(())
-}
+    #[test]
+    fn foreign_namespace_needs_both_links_to_differ() {
+        let host = Some("pid:[4026531836]");
+        let container = Some("pid:[4026532201]");
+        assert!(foreign_namespace(host, container));
+        assert!(!foreign_namespace(host, host));
+        assert!(!foreign_namespace(host, None));
+        assert!(!foreign_namespace(None, container));
+    }
 
-fn refresh_processes(sys: &mut System, filter: Option<&str>, sort_by: SortBy) -> Vec<ProcessInfo> {
-    sys.refresh_all();
-    thread::sleep(Duration::from_millis(200));
-    sys.refresh_all();
+    #[test]
+    fn cpu_mask_decodes_to_core_ranges() {
+        let cores = |mask| format_core_list(&parse_cpu_mask(mask).unwrap());
+        assert_eq!(cores("f"), "0-3");
+        assert_eq!(cores("00000005\n"), "0,2");
+        assert_eq!(cores("1,00000000"), "32");
+        assert_eq!(cores("ff0f"), "0-3,8-15");
+        assert_eq!(cores("0"), "");
+        assert!(parse_cpu_mask("zz").is_none());
+    }
 
-    let name_width = calculate_name_width(false);
+    #[test]
+    fn csv_rows_quote_awkward_names() {
+        let proc = |pid, name: &str| ProcessInfo {
+            pid,
+            name: name.to_string(),
+            cpu: 1.25,
+            memory: 3 * MB,
+            ..Default::default()
+        };
+        assert_eq!(
+            csv_rows(&[proc(1, "node"), proc(2, "say \"hi\", bye")], 1700000000),
+            "1700000000,1,node,1.2,3\n1700000000,2,\"say \"\"hi\"\", bye\",1.2,3\n"
+        );
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a\nb"), "\"a\nb\"");
+    }
 
-    let mut processes: Vec<ProcessInfo> = sys
-        .processes()
-        .iter()
-        .filter_map(|(pid, proc)| {
-            let name = proc.name().to_string_lossy().to_string();
+    #[test]
+    fn open_csv_log_writes_the_header_once() {
+        let path = temp_path("csv-log");
+        let _ = std::fs::remove_file(&path);
+        for _ in 0..2 {
+            let mut log = open_csv_log(&path).unwrap();
+            log.write_all(csv_rows(&[], 1).as_bytes()).unwrap();
+        }
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, CSV_HEADER);
+    }
 
-            if let Some(f) = filter {
-                if !name.to_lowercase().contains(&f.to_lowercase()) {
-                    return None;
-                }
+    #[test]
+    fn confirm_style_falls_back_to_plain_without_terminals() {
+        let tty = Terminals {
+            stdin: true,
+            stdout: true,
+            stderr: true,
+        };
+        let piped = Terminals {
+            stdin: false,
+            ..tty
+        };
+        assert_eq!(resolve_confirm_style(None, tty, false), ConfirmStyle::Fancy);
+        assert_eq!(resolve_confirm_style(None, tty, true), ConfirmStyle::Plain);
+        assert_eq!(
+            resolve_confirm_style(None, piped, false),
+            ConfirmStyle::Plain
+        );
+        assert_eq!(
+            resolve_confirm_style(Some(ConfirmStyle::Fancy), piped, true),
+            ConfirmStyle::Fancy
+        );
+    }
+
+    #[test]
+    fn parse_yes_uses_the_default_on_empty_input() {
+        assert!(parse_yes(" Y\n", false));
+        assert!(parse_yes("yes", false));
+        assert!(parse_yes("\n", true));
+        assert!(!parse_yes("", false));
+        assert!(!parse_yes("nope", true));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn holds_open_follows_fd_symlinks() {
+        let root = temp_path("open-files");
+        let _ = std::fs::remove_dir_all(&root);
+        let fd = root.join("fd");
+        std::fs::create_dir_all(&fd).unwrap();
+        std::os::unix::fs::symlink("/mnt/usb/photos/a.jpg", fd.join("3")).unwrap();
+        std::os::unix::fs::symlink("socket:[1234]", fd.join("4")).unwrap();
+
+        let held = holds_open(&fd, Path::new("/mnt/usb"));
+        let file = holds_open(&fd, Path::new("/mnt/usb/photos/a.jpg"));
+        let other = holds_open(&fd, Path::new("/mnt/usb2"));
+        let unreadable = holds_open(&root.join("missing"), Path::new("/"));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(held && file);
+        assert!(!other);
+        assert!(!unreadable);
+        assert!(!open_path_matches(
+            Path::new("/mnt/usbdrive"),
+            Path::new("/mnt/usb")
+        ));
+    }
+
+    #[test]
+    fn legend_closes_on_its_toggle_key() {
+        assert!(closes_overlay(KeyCode::Char('L'), 'L'));
+        assert!(closes_overlay(KeyCode::Esc, 'L'));
+        assert!(closes_overlay(KeyCode::Char('q'), 'L'));
+        assert!(!closes_overlay(KeyCode::Char('l'), 'L'));
+        assert!(!closes_overlay(KeyCode::Char('w'), 'L'));
+    }
+
+    #[test]
+    fn truncated_comm_is_recovered_from_exe_or_argv() {
+        let exe = Path::new("/usr/bin/my-long-service");
+        assert_eq!(
+            resolve_name("my-long-servic", Some(exe), None),
+            "my-long-servic"
+        );
+        assert_eq!(
+            resolve_name("my-long-service", None, None),
+            "my-long-service"
+        );
+        assert_eq!(
+            resolve_name(
+                "postgres-export",
+                Some(Path::new("/opt/postgres-exporter")),
+                None
+            ),
+            "postgres-exporter"
+        );
+        assert_eq!(
+            resolve_name("node-red-worker", None, Some("./node-red-worker-pool")),
+            "node-red-worker-pool"
+        );
+        // A 15-byte interpreter name that isn't a prefix stays as it is.
+        assert_eq!(
+            resolve_name("my-renamed-proc", Some(Path::new("/usr/bin/python3")), None),
+            "my-renamed-proc"
+        );
+    }
+
+    #[test]
+    fn single_protocol_needs_every_port_row_to_agree() {
+        let row = |port: Option<u16>, protocol: &str| ProcessInfo {
+            port,
+            protocol: Some(protocol.to_string()),
+            ..Default::default()
+        };
+        let tcp = [
+            row(Some(80), "TCP"),
+            row(Some(443), "TCP"),
+            row(None, "UDP"),
+        ];
+        assert_eq!(single_protocol(&tcp), Some("TCP"));
+        assert_eq!(single_protocol(&[row(Some(53), "UDP")]), Some("UDP"));
+        assert_eq!(
+            single_protocol(&[row(Some(80), "TCP"), row(Some(53), "UDP")]),
+            None
+        );
+        assert_eq!(single_protocol(&[]), None);
+
+        let mut columns = Columns::default();
+        assert_eq!(port_header(columns), "PORT");
+        columns.single_protocol = Some("TCP");
+        assert_eq!(port_header(columns), "PORT/TCP");
+    }
+
+    #[test]
+    fn port_ranges_parse_inclusively() {
+        let range = parse_port_range("3000-3010").unwrap();
+        assert!(range.contains(3000) && range.contains(3010));
+        assert!(!range.contains(3011));
+        assert_eq!(
+            parse_port_range(" 80 - 80 ").unwrap(),
+            PortRange { start: 80, end: 80 }
+        );
+        assert_eq!(
+            parse_port_range("3000").unwrap_err(),
+            "expected START-END, got 3000"
+        );
+        assert_eq!(parse_port_range("10-x").unwrap_err(), "invalid port x");
+        assert_eq!(
+            parse_port_range("90-80").unwrap_err(),
+            "range start 90 is above its end 80"
+        );
+    }
+
+    #[test]
+    fn pids_holding_several_ports_are_signalled_once() {
+        let row = |pid, port| ProcessInfo {
+            pid,
+            port: Some(port),
+            ..Default::default()
+        };
+        let selected = vec![row(7, 3000), row(7, 3001), row(8, 3002), row(7, 3003)];
+        let held = held_ports(&selected);
+        assert_eq!((held[&7], held[&8]), (3, 1));
+        let targets: Vec<u32> = dedupe_pids(selected).iter().map(|p| p.pid).collect();
+        assert_eq!(targets, [7, 8]);
+    }
+
+    #[test]
+    fn last_active_sort_puts_recent_activity_first() {
+        let proc = |pid, cpu| ProcessInfo {
+            pid,
+            cpu,
+            ..Default::default()
+        };
+        let mut seen = HashMap::new();
+        let mut rows = vec![proc(1, 5.0), proc(2, 0.0), proc(3, 1.0)];
+        record_activity(&mut rows, &mut seen, 1);
+        let mut rows = vec![proc(1, 0.0), proc(2, 0.0), proc(3, 2.0), proc(4, 0.0)];
+        record_activity(&mut rows, &mut seen, 2);
+        assert_eq!(rows[0].last_active, Some(1));
+        assert_eq!(rows[1].last_active, None);
+
+        sort_processes(&mut rows, SortBy::LastActive);
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [3, 1, 2, 4]);
+
+        // Gone PIDs are forgotten, so a reused PID starts out never-active.
+        record_activity(&mut [proc(3, 0.0)], &mut seen, 3);
+        assert!(!seen.contains_key(&1));
+    }
+
+    #[test]
+    fn invalid_signal_reports_a_json_error() {
+        let message = format!("Error: {}", parse_signal("sigbogus").unwrap_err());
+        assert_eq!(
+            error_json(&message, "invalid_signal"),
+            r#"{"error":"Unknown signal: BOGUS","code":"invalid_signal"}"#
+        );
+        assert_eq!(EXIT_ERROR, 1);
+    }
+
+    #[test]
+    fn json_string_escapes_quotes_and_control_characters() {
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+        assert_eq!(json_string("plain"), "\"plain\"");
+    }
+
+    #[test]
+    fn listen_specs_parse_protocol_and_port() {
+        assert_eq!(
+            parse_listen_spec("tcp:443").unwrap(),
+            ListenSpec {
+                protocol: "TCP",
+                port: 443
+            }
+        );
+        assert_eq!(
+            parse_listen_spec("UDP:53").unwrap(),
+            ListenSpec {
+                protocol: "UDP",
+                port: 53
             }
+        );
+        assert_eq!(
+            parse_listen_spec("sctp:80").unwrap_err(),
+            "unknown protocol sctp (expected tcp or udp)"
+        );
+        assert_eq!(
+            parse_listen_spec("tcp:99999").unwrap_err(),
+            "invalid port 99999"
+        );
+        assert_eq!(
+            parse_listen_spec("443").unwrap_err(),
+            "expected PROTO:PORT, got 443"
+        );
+    }
 
-            Some(ProcessInfo {
-                pid: pid.as_u32(),
-                name,
-                cpu: proc.cpu_usage(),
-                memory: proc.memory() / 1024 / 1024,
-                name_width,
-                port: None,
-                protocol: None,
-            })
-        })
-        .collect();
+    #[test]
+    fn listen_spec_matches_port_and_protocol_together() {
+        let row = |port, protocol: &str| ProcessInfo {
+            port: Some(port),
+            protocol: Some(protocol.to_string()),
+            ..Default::default()
+        };
+        let dns = parse_listen_spec("udp:53").unwrap();
+        assert!(dns.matches(&row(53, "UDP")));
+        assert!(!dns.matches(&row(53, "TCP")));
+        assert!(!dns.matches(&row(5353, "UDP")));
+    }
 
-    sort_processes(&mut processes, sort_by);
-    processes
-}
+    #[test]
+    fn hidden_selected_counts_marked_rows_outside_the_view() {
+        let rows: Vec<ProcessInfo> = [10, 11, 12, 13]
+            .into_iter()
+            .map(|pid| ProcessInfo {
+                pid,
+                ..Default::default()
+            })
+            .collect();
+        let selected: HashSet<u32> = [10, 12, 13].into();
+        assert_eq!(hidden_selected(&selected, &rows, &[0, 1, 2, 3]), 0);
+        assert_eq!(hidden_selected(&selected, &rows, &[0, 1]), 2);
+        assert_eq!(hidden_selected(&selected, &rows, &[]), 3);
+        assert_eq!(hidden_selected(&HashSet::new(), &rows, &[]), 0);
+    }
 
-fn refresh_processes_with_ports(
-    sys: &mut System,
-    filter: Option<&str>,
-    port_filter: Option<u16>,
-    sort_by: SortBy,
-) -> Vec<ProcessInfo> {
-    sys.refresh_all();
-    thread::sleep(Duration::from_millis(200));
-    sys.refresh_all();
+    #[test]
+    fn session_members_collects_every_pid_in_the_sessions() {
+        let stats = [
+            (100, 100),
+            (101, 100),
+            (102, 100),
+            (200, 200),
+            (201, 200),
+            (2, 0),
+        ];
+        let mut members = session_members(&stats, &[100].into());
+        members.sort_unstable();
+        assert_eq!(members, [100, 101, 102]);
+        // Kernel threads report session 0 and never count.
+        assert!(session_members(&stats, &[0].into()).is_empty());
+        assert!(session_members(&stats, &HashSet::new()).is_empty());
+    }
 
-    let port_map = get_port_mappings();
-    let name_width = calculate_name_width(true);
+    #[test]
+    fn diff_snapshots_reports_added_removed_and_changed() {
+        let old = parse_snapshot(
+            "#pid\tname\tcpu\tmemory_mb\tport\tprotocol\towner\tstart_time\tcommand\n\
+             1\tinit\t0.0\t10\t\t\troot\t100\t/sbin/init\n\
+             2\tnode\t5.0\t200\t3000\tTCP\tdev\t200\tnode app.js\n\
+             2\tnode\t5.0\t200\t3001\tTCP\tdev\t200\tnode app.js\n\
+             3\tvim\t0.0\t30\t\t\tdev\t300\tvim\n\
+             4\tcron\t0.0\t5\t\t\troot\t400\tcron\n",
+        )
+        .unwrap();
+        // PID 3 was reused by a new process and node's CPU jumped.
+        let new = parse_snapshot(
+            "1\tinit\t0.5\t12\t\t\troot\t100\t/sbin/init\n\
+             2\tnode\t60.0\t210\t3000\tTCP\tdev\t200\tnode app.js\n\
+             3\tbash\t0.0\t4\t\t\tdev\t900\tbash\n\
+             5\tsshd\t0.0\t8\t\t\troot\t500\tsshd\n",
+        )
+        .unwrap();
+        let diff = diff_snapshots(&old, &new);
+        let pids = |rows: &[SnapshotRow]| {
+            rows.iter()
+                .map(|r| (r.pid, r.name.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            pids(&diff.added),
+            [(3, "bash".to_string()), (5, "sshd".to_string())]
+        );
+        assert_eq!(
+            pids(&diff.removed),
+            [(3, "vim".to_string()), (4, "cron".to_string())]
+        );
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!((diff.changed[0].0.cpu, diff.changed[0].1.cpu), (5.0, 60.0));
+    }
 
-    let mut processes: Vec<ProcessInfo> = sys
-        .processes()
-        .iter()
-        .flat_map(|(pid, proc)| {
-            let pid_u32 = pid.as_u32();
-            let ports = match port_map.get(&pid_u32) {
-                Some(p) => p,
-                None => return vec![],
-            };
+    #[test]
+    fn parse_snapshot_names_the_bad_row() {
+        assert_eq!(
+            parse_snapshot("1\ta\n2x\tb\n").unwrap_err(),
+            "row 2: invalid pid \"2x\""
+        );
+    }
 
-            let name = proc.name().to_string_lossy().to_string();
+    #[test]
+    fn refresh_mode_falls_back_to_polling_without_events() {
+        assert_eq!(refresh_mode(0, true, false), RefreshMode::OnChange);
+        assert_eq!(
+            refresh_mode(0, false, false),
+            RefreshMode::Every(Duration::from_millis(2000))
+        );
+        assert_eq!(
+            refresh_mode(0, false, true),
+            RefreshMode::Every(Duration::from_millis(SSH_INTERVAL_MS))
+        );
+        assert_eq!(
+            refresh_mode(750, true, false),
+            RefreshMode::Every(Duration::from_millis(750))
+        );
+    }
 
-            if let Some(f) = filter {
-                if !name.to_lowercase().contains(&f.to_lowercase()) {
-                    return vec![];
-                }
+    #[test]
+    fn on_change_refreshes_are_rate_limited() {
+        let soon = ON_CHANGE_MIN_INTERVAL / 2;
+        let mut checked = false;
+        assert!(!refresh_due(
+            RefreshMode::OnChange,
+            soon,
+            Duration::ZERO,
+            || {
+                checked = true;
+                true
             }
+        ));
+        assert!(!checked, "the table is not read before the minimum spacing");
+        let later = ON_CHANGE_MIN_INTERVAL;
+        assert!(refresh_due(
+            RefreshMode::OnChange,
+            later,
+            Duration::ZERO,
+            || true
+        ));
+        assert!(!refresh_due(
+            RefreshMode::OnChange,
+            later,
+            Duration::ZERO,
+            || false
+        ));
+    }
 
-            let cpu = proc.cpu_usage();
-            let memory = proc.memory() / 1024 / 1024;
+    #[test]
+    fn timed_refreshes_ignore_the_process_table() {
+        let mode = RefreshMode::Every(Duration::from_secs(2));
+        let interval = Duration::from_secs(2);
+        assert!(!refresh_due(mode, Duration::from_secs(1), interval, || {
+            true
+        }));
+        assert!(refresh_due(mode, Duration::from_secs(2), interval, || {
+            false
+        }));
+    }
 
-            ports
-                .iter()
-                .filter_map(|(port, protocol)| {
-                    if let Some(target_port) = port_filter {
-                        if *port != target_port {
-                            return None;
-                        }
-                    }
+    #[test]
+    fn cpu_time_formats_as_hours_minutes_seconds() {
+        assert_eq!(format_cpu_time(42), "0:00:42");
+        assert_eq!(format_cpu_time(754), "0:12:34");
+        assert_eq!(format_cpu_time(3 * 3600 + 5 * 60 + 9), "3:05:09");
+        assert_eq!(format_cpu_time(100 * 3600), "100:00:00");
+        assert_eq!(optional_cpu_time(None), "-");
+        assert_eq!(cpu_seconds(12_345, 100.0), 123);
+    }
 
-                    Some(ProcessInfo {
-                        pid: pid_u32,
-                        name: name.clone(),
-                        cpu,
-                        memory,
-                        name_width,
-                        port: Some(*port),
-                        protocol: Some(protocol.clone()),
-                    })
+    #[test]
+    fn cpu_time_sort_puts_unknown_times_last() {
+        let timed = |pid, cpu_time| ProcessInfo {
+            pid,
+            cpu_time,
+            ..Default::default()
+        };
+        let mut rows = vec![timed(1, Some(5)), timed(2, None), timed(3, Some(900))];
+        sort_processes(&mut rows, SortBy::CpuTime);
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [3, 1, 2]);
+    }
+
+    #[test]
+    fn explicit_pid_selection_only_covers_named_pids() {
+        let rows = |pids: &[u32]| -> Vec<ProcessInfo> {
+            pids.iter()
+                .map(|&pid| ProcessInfo {
+                    pid,
+                    ..Default::default()
                 })
-                .collect::<Vec<_>>()
-        })
-        .collect();
+                .collect()
+        };
+        assert!(explicit_pid_selection(&[10, 11], &rows(&[10, 11])));
+        assert!(explicit_pid_selection(&[10, 11], &rows(&[10])));
+        assert!(!explicit_pid_selection(&[], &rows(&[10])));
+        assert!(!explicit_pid_selection(&[10], &rows(&[10, 12])));
+    }
 
-    sort_processes(&mut processes, sort_by);
-    processes
-}
+    #[test]
+    fn expanded_selection_is_no_longer_explicit() {
+        let mut selected = vec![ProcessInfo {
+            pid: 10,
+            ..Default::default()
+        }];
+        assert!(explicit_pid_selection(&[10], &selected));
+        selected.push(ProcessInfo {
+            pid: 11,
+            parent: Some(10),
+            ..Default::default()
+        });
+        assert!(!explicit_pid_selection(&[10], &selected));
+        assert!(explicit_pid_selection(&[10, 11], &selected));
+    }
 
-fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
-    let popup_layout = Layout::vertical([
-        Constraint::Percentage((100 - percent_y) / 2),
-        Constraint::Percentage(percent_y),
-        Constraint::Percentage((100 - percent_y) / 2),
-    ])
-    .split(r);
+    #[test]
+    fn socket_total_counts_only_socket_links() {
+        let links = [
+            "socket:[12345]",
+            "/dev/null",
+            "pipe:[999]",
+            "socket:[12346]",
+            "anon_inode:[eventfd]",
+        ];
+        assert_eq!(socket_total(links.iter().map(Path::new)), 2);
+        assert_eq!(socket_total(Vec::<PathBuf>::new()), 0);
+        assert!(!is_socket_link(Path::new("/tmp/socket:[1]")));
+    }
 
-    Layout::horizontal([
-        Constraint::Percentage((100 - percent_x) / 2),
-        Constraint::Percentage(percent_x),
-        Constraint::Percentage((100 - percent_x) / 2),
-    ])
-    .split(popup_layout[1])[1]
-}
+    #[test]
+    fn socket_sort_waits_for_the_enriched_counts() {
+        assert!(sorts_after_enrich(SortBy::Sockets));
+        assert!(!sorts_after_enrich(SortBy::Cpu));
+        let sockets = |pid, count| ProcessInfo {
+            pid,
+            socket_count: count,
+            ..Default::default()
+        };
+        let mut rows = vec![sockets(1, Some(2)), sockets(2, None), sockets(3, Some(40))];
+        sort_processes(&mut rows, SortBy::Sockets);
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [3, 1, 2]);
+    }
 
-fn kill_processes(selected: Vec<ProcessInfo>, _signal: Signal) {
-    let s = System::new_all();
+    const UNICODE_NAME: &str = "café_server_😀_daemon";
 
-    for proc in selected {
-        let mut killed = false;
+    #[test]
+    fn truncate_keeps_unicode_names_that_fit() {
+        assert_eq!(truncate(UNICODE_NAME, 20), UNICODE_NAME);
+        assert_eq!(truncate(UNICODE_NAME, 40), UNICODE_NAME);
+    }
 
-        #[cfg(unix)]
-        {
-            use nix::unistd::Pid as NixPid;
-            use nix::sys::signal::kill as nix_kill;
-            if nix_kill(NixPid::from_raw(proc.pid as i32), signal).is_ok() {
-                killed = true;
-            }
-        }
+    #[test]
+    fn truncate_cuts_unicode_names_on_char_boundaries() {
+        assert_eq!(truncate(UNICODE_NAME, 19), "café_server_😀_da...");
+        assert_eq!(truncate(UNICODE_NAME, 16), "café_server_😀...");
+        assert_eq!(truncate(UNICODE_NAME, 15), "café_server_...");
+        assert_eq!(truncate(UNICODE_NAME, 7), "café...");
+        assert_eq!(truncate(UNICODE_NAME, 3), "caf");
+        assert_eq!(truncate(UNICODE_NAME, 0), "");
+    }
 
-        #[cfg(windows)]
-        {
-            if let Some(process) = s.process(sysinfo::Pid::from_u32(proc.pid)) {
-                killed = process.kill();
+    #[test]
+    fn truncate_never_exceeds_the_width_in_chars() {
+        for strategy in [Truncate::End, Truncate::Start, Truncate::Middle] {
+            for width in 0..=24 {
+                let cut = truncate_with(UNICODE_NAME, width, strategy);
+                assert!(cut.chars().count() <= width, "{:?} at {}", strategy, width);
             }
         }
+    }
 
-        if killed {
-            println!(
-                "{} {} {}",
-                Colorize::green("Killed"),
-                Colorize::bold(proc.name.as_str()),
-                Colorize::dimmed(format!("(PID: {})", proc.pid).as_str())
-            );
-        } else {
-            eprintln!(
-                "{} {} {}",
-                Colorize::red("Failed"),
-                Colorize::bold(proc.name.as_str()),
-                Colorize::dimmed(format!("(PID: {})", proc.pid).as_str())
-            );
-        }
+    #[test]
+    fn truncate_with_start_and_middle_on_unicode() {
+        assert_eq!(
+            truncate_with(UNICODE_NAME, 10, Truncate::Start),
+            "..._daemon"
+        );
+        assert_eq!(
+            truncate_with(UNICODE_NAME, 10, Truncate::Middle),
+            "café...mon"
+        );
+        assert_eq!(
+            truncate_with(UNICODE_NAME, 11, Truncate::Start),
+            "...😀_daemon"
+        );
     }
-}
 
-fn validate_args(args: &Args) -> Result<(), String> {
-    if args.confirm_nuke && args.filter.is_none() && args.port.is_none() {
-        return Err("Error: Process --confirm-nuke requires a filter (-f, --filter) or a port (--port) to prevent accidental mass deletion.".to_string());
+    #[test]
+    fn pinned_processes_sort_ahead_in_the_chosen_order() {
+        let proc = |pid, name: &str, mb: u64| ProcessInfo {
+            pid,
+            name: name.to_string(),
+            memory: mb * MB,
+            ..Default::default()
+        };
+        let mut rows = vec![
+            proc(1, "chrome", 900),
+            proc(2, "Postgres", 100),
+            proc(3, "redis-server", 50),
+            proc(4, "postgres", 300),
+        ];
+        mark_pinned(&mut rows, &["POSTGRES".to_string(), "redis".to_string()]);
+        sort_processes(&mut rows, SortBy::Mem);
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [4, 2, 3, 1]);
+        assert!(rows[..3].iter().all(|p| p.pinned));
     }
-    Ok(())
-}
 
-fn main() {
-    let args = Args::parse();
+    #[test]
+    fn nan_cpu_sorts_last_without_panicking() {
+        let proc = |pid, cpu| ProcessInfo {
+            pid,
+            cpu,
+            ..Default::default()
+        };
+        let mut rows = vec![
+            proc(1, f32::NAN),
+            proc(2, 3.0),
+            proc(3, 0.0),
+            proc(4, f32::NAN),
+        ];
+        sort_processes(&mut rows, SortBy::Cpu);
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [2, 3, 1, 4]);
+        sort_processes(&mut rows, SortBy::LastActive);
+        assert_eq!(rows[0].pid, 2);
+        assert_eq!(cmp_cpu(f32::NAN, 0.0), std::cmp::Ordering::Less);
+        assert_eq!(cmp_cpu(f32::NAN, f32::NAN), std::cmp::Ordering::Equal);
+    }
 
-    if let Err(e) = validate_args(&args) {
-        eprintln!("{}", Colorize::red(e.as_str()));
-        std::process::exit(1);
+    #[test]
+    fn kill_exit_code_maps_outcomes() {
+        assert_eq!(kill_exit_code(0, 0, false), 0);
+        assert_eq!(kill_exit_code(3, 3, false), 0);
+        assert_eq!(kill_exit_code(3, 2, false), EXIT_KILL_FAILED);
+        assert_eq!(kill_exit_code(3, 1, true), EXIT_ABORTED);
     }
 
-    let signal = match parse_signal(&args.signal) {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
-        }
-    };
+    #[test]
+    fn empty_selection_code_separates_abort_from_no_match() {
+        assert_eq!(empty_selection_code(false, false), EXIT_ABORTED);
+        assert_eq!(empty_selection_code(true, true), EXIT_ABORTED);
+        assert_eq!(empty_selection_code(true, false), EXIT_NO_MATCH);
+    }
+
+    #[test]
+    fn exit_codes_are_distinct_and_ordered_by_severity() {
+        let codes = [0, EXIT_ERROR, EXIT_NO_MATCH, EXIT_KILL_FAILED, EXIT_ABORTED];
+        assert_eq!(codes, [0, 1, 2, 3, 4]);
+        assert_eq!(0.max(EXIT_KILL_FAILED).max(EXIT_ABORTED), EXIT_ABORTED);
+    }
 
-    let ports_mode = args.ports || args.port.is_some();
-    let port_filter = args.port;
+    #[test]
+    fn sampler_reuses_one_system_across_refreshes() {
+        let mut sampler = ProcessSampler::new(false, MemKind::Rss);
+        assert!(sampler.system().processes().is_empty());
+        let own = std::process::id();
+        let first = sampler.processes(None, SortBy::Pid);
+        assert!(first.iter().any(|p| p.pid == own));
+        let stamp = sampler.last_refresh;
+        assert!(stamp.is_some());
+        let second = sampler.processes(None, SortBy::Pid);
+        assert!(second.iter().any(|p| p.pid == own));
+        assert!(sampler.last_refresh > stamp);
+    }
 
-    if args.live {
-        if let Err(e) = run_live_mode(
-            args.filter.as_deref(),
-            args.sort,
-            signal,
-            ports_mode,
-            port_filter,
-        ) {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
-        }
-        return;
+    #[test]
+    fn open_files_limit_parses_the_soft_limit() {
+        let limits = "Limit                     Soft Limit           Hard Limit           Units     \n\
+                      Max cpu time              unlimited            unlimited            seconds   \n\
+                      Max open files            1024                 524288               files     \n";
+        assert_eq!(parse_open_files_limit(limits), Some(1024));
+        assert_eq!(
+            parse_open_files_limit(
+                "Max open files            unlimited            unlimited            files\n"
+            ),
+            None
+        );
+        assert_eq!(parse_open_files_limit(""), None);
     }
 
-    let processes = if ports_mode {
-        get_processes_with_ports(args.filter.as_deref(), port_filter, args.sort)
-    } else {
-        get_processes(args.filter.as_deref(), args.sort)
-    };
+    #[test]
+    fn fd_percent_compares_open_fds_with_the_limit() {
+        assert_eq!(fd_percent(512, 1024), Some(50.0));
+        assert_eq!(fd_percent(3, 0), None);
+        assert_eq!(fd_usage_label(fd_percent(1000, 1024)), "97.7%");
+        assert_eq!(fd_usage_label(None), "-");
+        assert!(fd_percent(900, 1024).unwrap() > FD_USAGE_WARN);
+    }
 
-    if processes.is_empty() {
-        if ports_mode {
-            println!("No processes with listening ports found");
-        } else {
-            println!("No processes found");
-        }
-        return;
+    #[test]
+    fn live_name_width_follows_the_terminal_width() {
+        let columns = Columns::default();
+        let narrow = live_name_width(70, columns, false);
+        let wide = live_name_width(100, columns, false);
+        assert_eq!(wide - narrow, 30);
+        assert!(live_name_width(100, columns, true) < wide);
+        // Shrinking past the fixed columns clamps instead of underflowing.
+        assert_eq!(live_name_width(10, columns, false), 15);
+        assert_eq!(live_name_width(1000, columns, false), 80);
     }
 
-    let selected = if args.confirm_nuke {
-        processes
-    } else {
-        run_selector(processes, ports_mode)
-    };
+    #[test]
+    fn extra_columns_take_width_from_the_name() {
+        let plain = Columns::default();
+        let with_owner = Columns {
+            owner: true,
+            ..plain
+        };
+        assert_eq!(
+            column_name_width(60, plain) - column_name_width(60, with_owner),
+            OWNER_WIDTH + 1
+        );
+        assert_eq!(column_name_width(16, with_owner), 15);
+        let counts = Columns {
+            children: true,
+            sockets: true,
+            ..plain
+        };
+        assert_eq!(
+            column_name_width(60, plain) - column_name_width(60, counts),
+            CHILDREN_WIDTH + SOCKETS_WIDTH + 2
+        );
+    }
 
-    if selected.is_empty() {
-        println!("No processes selected");
-        return;
+    #[test]
+    fn format_memory_picks_the_unit_by_size() {
+        assert_eq!(format_memory(0), "0 KB");
+        assert_eq!(format_memory(900 * 1024), "900 KB");
+        assert_eq!(format_memory(MB - 1), "1023 KB");
+        assert_eq!(format_memory(MB), "1.0 MB");
+        assert_eq!(format_memory(MB + MB / 2), "1.5 MB");
+        assert_eq!(format_memory(1024 * MB - MB), "1023.0 MB");
+        assert_eq!(format_memory(1024 * MB), "1.0 GB");
+        assert_eq!(format_memory(1536 * MB), "1.5 GB");
+    }
+
+    #[test]
+    fn needs_cpu_only_when_something_reads_it() {
+        let needs = |argv: &[&str]| needs_cpu(&Args::try_parse_from(argv).unwrap());
+        assert!(needs(&["rip"]));
+        assert!(!needs(&["rip", "--sort", "mem"]));
+        assert!(needs(&["rip", "--sort", "last-active"]));
+        assert!(needs(&["rip", "--sort", "pid", "--selector-sort", "cpu"]));
+        assert!(needs(&["rip", "--sort", "name", "--summary"]));
+        assert!(needs(&["rip", "--sort", "name", "--output", "tsv"]));
+        assert!(needs(&["rip", "--dedupe-by", "name", "--sort", "name"]));
+        assert!(needs(&[
+            "rip",
+            "--sort",
+            "mem",
+            "--query",
+            "mem > 5 or cpu > 1"
+        ]));
+        assert!(!needs(&[
+            "rip",
+            "--sort",
+            "mem",
+            "--query",
+            "not name ~ cpu"
+        ]));
+    }
+
+    #[test]
+    fn rising_edge_fires_once_per_new_match() {
+        let cooldown = Duration::from_secs(10);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut edge = RisingEdge::default();
+        assert!(!edge.update(false, at(0), cooldown));
+        assert!(edge.update(true, at(1), cooldown));
+        assert!(!edge.update(true, at(2), cooldown));
+        assert!(!edge.update(false, at(3), cooldown));
+        // A flap inside the cooldown is debounced...
+        assert!(!edge.update(true, at(4), cooldown));
+        assert!(!edge.update(false, at(12), cooldown));
+        // ...but the next rise after it fires again.
+        assert!(edge.update(true, at(13), cooldown));
+    }
+
+    #[test]
+    fn on_match_command_only_passes_pids_when_asked() {
+        assert_eq!(
+            on_match_command("notify-send hog", &[4, 5]),
+            ["notify-send", "hog"]
+        );
+        assert_eq!(
+            on_match_command("kill -STOP {}", &[4, 5]),
+            ["kill", "-STOP", "4", "5"]
+        );
+        assert_eq!(
+            on_match_command("log pids={}", &[4, 5]),
+            ["log", "pids=4 5"]
+        );
+    }
+
+    #[test]
+    fn missing_pids_lists_each_unmatched_pid_once() {
+        let matched: Vec<ProcessInfo> = [10, 30]
+            .into_iter()
+            .map(|pid| ProcessInfo {
+                pid,
+                ..Default::default()
+            })
+            .collect();
+        assert_eq!(missing_pids(&[10, 20, 30, 40, 20], &matched), [20, 40]);
+        assert!(missing_pids(&[10, 30], &matched).is_empty());
+        assert_eq!(missing_pids(&[5], &[]), [5]);
+    }
+
+    #[test]
+    fn pids_are_repeatable_on_the_command_line() {
+        let args = Args::try_parse_from(["rip", "--pid", "10", "--pid", "20"]).unwrap();
+        assert_eq!(args.pids, [10, 20]);
+    }
+
+    #[test]
+    fn nice_in_range_applies_both_bounds() {
+        assert!(nice_in_range(Some(0), None, None));
+        assert!(nice_in_range(Some(-5), Some(-10), Some(0)));
+        assert!(nice_in_range(Some(19), Some(10), None));
+        assert!(!nice_in_range(Some(5), Some(10), None));
+        assert!(!nice_in_range(Some(5), None, Some(-1)));
+        assert!(!nice_in_range(None, None, Some(19)));
+        assert_eq!(optional_nice(Some(-20)), "-20");
+        assert_eq!(optional_nice(None), "-");
+    }
+
+    #[test]
+    fn prio_flags_filter_the_listing() {
+        let niced = |pid, nice| ProcessInfo {
+            pid,
+            nice,
+            ..Default::default()
+        };
+        let mut rows = vec![
+            niced(1, Some(-5)),
+            niced(2, Some(0)),
+            niced(3, Some(19)),
+            niced(4, None),
+        ];
+        let args = Args::try_parse_from(["rip", "--min-prio", "-10", "--max-prio", "0"]).unwrap();
+        apply_filters(&mut rows, &args, Path::new("/proc"));
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn legend_uses_the_active_theme() {
+        let theme = Theme::named(ThemeName::Light);
+        let entries = legend(theme);
+        let udp = entries
+            .iter()
+            .find(|(_, _, text)| *text == "UDP socket")
+            .unwrap();
+        assert_eq!(udp.1.fg, Some(theme.udp));
+    }
+
+    #[test]
+    fn themes_paint_a_hot_cell_differently() {
+        let dark = Theme::named(ThemeName::Dark);
+        let light = Theme::named(ThemeName::Light);
+        let contrast = Theme::named(ThemeName::HighContrast);
+        let mono = Theme::named(ThemeName::Mono);
+        assert_ne!(dark, light);
+        assert_ne!(dark, contrast);
+        assert_ne!(dark.hot, dark.good);
+        assert_eq!(mono.hot, Color::Reset);
+        assert_eq!(mono.good, Color::Reset);
+        colored::control::set_override(true);
+        let hot = paint("99.0", dark.hot).to_string();
+        let plain = paint("99.0", mono.hot).to_string();
+        colored::control::unset_override();
+        assert_ne!(hot, "99.0");
+        assert_eq!(plain, "99.0");
+    }
+
+    #[test]
+    fn tree_kill_order_signals_children_before_parents() {
+        let parents: HashMap<u32, u32> = [(11, 10), (12, 10), (13, 11), (21, 20)].into();
+        let children = children_map(&parents);
+        assert_eq!(children[&10], [11, 12]);
+        assert_eq!(tree_kill_order(&[10], &children), [13, 11, 12, 10]);
+        // A root inside another root's subtree is listed once.
+        assert_eq!(
+            tree_kill_order(&[10, 11, 20], &children),
+            [13, 11, 12, 10, 21, 20]
+        );
+        assert_eq!(tree_kill_order(&[99], &children), [99]);
+    }
+
+    #[test]
+    fn owner_label_truncates_and_falls_back_to_dash() {
+        let owned = |owner: Option<&str>| ProcessInfo {
+            owner: owner.map(str::to_string),
+            ..Default::default()
+        };
+        assert_eq!(owner_label(&owned(Some("root"))), "root");
+        assert_eq!(owner_label(&owned(None)), "-");
+        let long = owner_label(&owned(Some("systemd-network")));
+        assert_eq!(long.chars().count(), OWNER_WIDTH);
+    }
+
+    #[test]
+    fn owner_column_reserves_header_space() {
+        let columns = Columns {
+            owner: true,
+            ..Columns::default()
+        };
+        assert!(selector_header(columns, 20, false).contains("OWNER"));
+        assert!(!selector_header(Columns::default(), 20, false).contains("OWNER"));
+    }
+
+    #[test]
+    fn loop_continues_until_nothing_is_selected() {
+        assert!(loop_continues(2, false));
+        assert!(!loop_continues(0, false));
+        assert!(!loop_continues(3, true));
+    }
+
+    #[test]
+    fn pid_zero_is_never_a_kill_target() {
+        assert!(is_pseudo_pid(0));
+        assert!(!is_pseudo_pid(1));
+        let row = |pid| ProcessInfo {
+            pid,
+            ..Default::default()
+        };
+        let targets: Vec<u32> = kill_targets(vec![row(0), row(42), row(0)])
+            .iter()
+            .map(|p| p.pid)
+            .collect();
+        assert_eq!(targets, [42]);
+        assert!(kill_processes(&Settings::default(), vec![row(0)], Signal::SIGTERM, &[]).is_empty());
+    }
+
+    #[test]
+    fn resolve_user_accepts_me_uids_and_names() {
+        let users = Users::new_with_refreshed_list();
+        let me = current_uid().unwrap().to_string();
+        assert_eq!(
+            resolve_user("me", &users).unwrap(),
+            owner_for_uid(&me, &users)
+        );
+        assert_eq!(resolve_user("3999999", &users).unwrap(), "3999999");
+        assert_eq!(
+            resolve_user("no-such-user-here", &users).unwrap_err(),
+            "Error: no such user: no-such-user-here"
+        );
+        if users.list().iter().any(|u| u.name() == "root") {
+            assert_eq!(resolve_user("0", &users).unwrap(), "root");
+            assert_eq!(resolve_user("root", &users).unwrap(), "root");
+        }
     }
 
-    kill_processes(selected, signal);
+    #[test]
+    fn user_filter_keeps_only_that_owner() {
+        let owned = |pid, owner: Option<&str>| ProcessInfo {
+            pid,
+            owner: owner.map(str::to_string),
+            ..Default::default()
+        };
+        let mut rows = vec![
+            owned(1, Some("root")),
+            owned(2, Some("dev")),
+            owned(3, None),
+        ];
+        let args = Args::try_parse_from(["rip", "--user", "dev", "-f", "node"]).unwrap();
+        apply_filters(&mut rows, &args, Path::new("/proc"));
+        assert_eq!(rows.iter().map(|p| p.pid).collect::<Vec<_>>(), [2]);
+    }
 }